@@ -0,0 +1,85 @@
+#ifndef GIF_PROBE_H
+#define GIF_PROBE_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// What a call returns, which is also why the probe failed, if it did
+enum GifProbeStatus {
+  GIF_PROBE_STATUS_OK = 0,
+  // `data` was `NULL` with a non-zero `len`, or `out` was `NULL`. Nothing is written to `out`.
+  GIF_PROBE_STATUS_INVALID_ARGUMENT = 1,
+  // The data isn't an image that could be decoded
+  GIF_PROBE_STATUS_DECODE = 2,
+  // The data is larger than `max_file_size`
+  GIF_PROBE_STATUS_FILE_TOO_LARGE = 3,
+  // The canvas has more pixels than `max_pixels`
+  GIF_PROBE_STATUS_TOO_LARGE = 4,
+  // Every frame's pixels add up to more than `max_expansion` per byte
+  GIF_PROBE_STATUS_TOO_EXPANSIVE = 5,
+};
+typedef int32_t GifProbeStatus;
+
+// A few of the summary fields, and the whole record as JSON
+typedef struct GifProbeResult {
+  bool alpha;
+  bool animated;
+  uint16_t width;
+  uint16_t height;
+  uint64_t frames;
+  // In centiseconds
+  uint64_t duration;
+  // In centiseconds, with delays as browsers play them
+  uint64_t duration_normalized;
+  uint64_t bytes_read;
+  // NUL-terminated JSON, the record the binary prints for the data, or an object with an `error` if probing
+  // failed. Owned by the library until passed to [`gif_probe_result_free`].
+  char *json;
+} GifProbeResult;
+
+// Limits for [`gif_probe_run_with`], where 0 means none, or the default for `max_memory`
+typedef struct GifProbeOptions {
+  // Stop after this many centiseconds of animation, like `-j`
+  uint64_t max_duration;
+  // Stop after this many frames, like `-f`
+  uint64_t max_frames;
+  // Fail with [`GifProbeStatus::TooLarge`] for canvases with more pixels than this, like `-d`
+  uint64_t max_pixels;
+  // Fail with [`GifProbeStatus::FileTooLarge`] for more bytes than this, like `--max-file-size`
+  uint64_t max_file_size;
+  // Fail with [`GifProbeStatus::TooExpansive`] past this ratio, like `--max-expansion`
+  double max_expansion;
+  // Don't let the decoder allocate more than this many bytes, 20 MiB by default, like `-m`
+  uint64_t max_memory;
+} GifProbeOptions;
+
+// Probes `len` bytes at `data` with the default options, writing the result to `out`.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, or may be `NULL` if `len` is 0, and `out` must point to a
+// writable [`GifProbeResult`]. Whatever `out` held before is overwritten without being freed.
+GifProbeStatus gif_probe_run(const uint8_t *data, size_t len, struct GifProbeResult *out);
+
+// Same as [`gif_probe_run`], with the limits in `options`, or the defaults if it's `NULL`.
+//
+// # Safety
+//
+// Same as for [`gif_probe_run`], and `options` must be `NULL` or point to a readable [`GifProbeOptions`].
+GifProbeStatus gif_probe_run_with(const uint8_t *data,
+                                  size_t len,
+                                  const struct GifProbeOptions *options,
+                                  struct GifProbeResult *out);
+
+// Frees the JSON of a result filled in by [`gif_probe_run`] or [`gif_probe_run_with`], setting it to `NULL`.
+// Does nothing if `result` or its JSON is `NULL`, so freeing twice is harmless.
+//
+// # Safety
+//
+// `result` must be `NULL` or point to a result written by this library, whose JSON hasn't been changed since.
+void gif_probe_result_free(struct GifProbeResult *result);
+
+#endif /* GIF_PROBE_H */
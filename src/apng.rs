@@ -0,0 +1,253 @@
+//! Still and animated PNG probing for the `apng` feature, filling in the same summary fields as for a GIF.
+//!
+//! Frame counts, delays and looping come straight from the chunks, so the image data is only decoded when
+//! a composited frame could have transparent pixels, to find out whether one actually does.
+
+use png::{BlendOp, DisposeOp, Transformations};
+
+use crate::{
+    probe::{Codec, GifProbe, STILL},
+    ProbeError, ProbeOptions,
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Whether the start of the input is the PNG signature
+pub fn sniff(magic: &[u8]) -> bool {
+    magic.starts_with(&SIGNATURE)
+}
+
+pub fn probe(data: &[u8], options: &ProbeOptions) -> Result<GifProbe, ProbeError> {
+    let limits = png::Limits {
+        bytes: options.memory_limit().get() as usize,
+    };
+
+    let mut decoder = png::Decoder::new_with_limits(data, limits);
+    // every pixel comes out as 8-bit gray or RGB, with an alpha channel if it has any transparency
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()?;
+    let (width, height) = (reader.info().width, reader.info().height);
+
+    if matches!(options.max_pixels, Some(m) if m < (width as u64 * height as u64)) {
+        return Err(ProbeError::TooLarge);
+    }
+
+    // the output has GIF's 16-bit dimensions
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(ProbeError::TooLarge);
+    };
+
+    let container = Container::parse(data);
+    let animated = container.num_plays.is_some() && !container.delays.is_empty();
+
+    let mut probe = GifProbe {
+        codec: Codec::Deflate,
+        width,
+        height,
+        has_color_profile: container.has_color_profile,
+        loop_count: container.num_plays.filter(|_| animated).map(|plays| plays.min(u16::MAX as u32) as u16),
+        has_xmp: container.has_xmp,
+        alpha_coverage: Some(0.0),
+        ..GifProbe::default()
+    };
+
+    match animated {
+        true => probe.add_delays(container.delays.iter().map(|&delay| (1, delay))),
+        false => probe.add_delays(STILL),
+    }
+
+    if container.maybe_transparent {
+        let (width, height) = (width as usize, height as usize);
+
+        let size = reader.output_buffer_size();
+
+        // the decoder's own limits don't cover the buffer it decodes into, or the canvas composited here
+        if (size as u64).saturating_add((width * height) as u64) > options.memory_limit().get() {
+            return Err(ProbeError::TooLarge);
+        }
+
+        let mut buffer = vec![0; size];
+        probe.memory_peak = buffer.len() as u64;
+        // only the alpha of the canvas matters, which starts out fully transparent
+        let mut canvas = vec![0u8; width * height];
+
+        let decodes = match animated {
+            true => container.delays.len() + container.hidden_default_image as usize,
+            false => 1,
+        };
+
+        let mut shown = 0;
+
+        for _ in 0..decodes {
+            options.check_cancelled()?;
+
+            let info = reader.next_frame(&mut buffer)?;
+
+            let (left, top, dispose, blend) = match reader.info().frame_control {
+                Some(control) if animated => (
+                    control.x_offset as usize,
+                    control.y_offset as usize,
+                    control.dispose_op,
+                    control.blend_op,
+                ),
+                // the default image isn't part of the animation
+                None if animated => continue,
+                _ => (0, 0, DisposeOp::None, BlendOp::Source),
+            };
+
+            let (frame_width, frame_height) = (info.width as usize, info.height as usize);
+            let samples = info.color_type.samples();
+
+            // disposing of the first frame this way is the same as disposing to the background
+            let previous = (dispose == DisposeOp::Previous).then(|| canvas.clone());
+
+            for (y, row) in buffer.chunks(info.line_size).take(frame_height).enumerate() {
+                let Some(canvas_row) = canvas.chunks_exact_mut(width).nth(top + y) else {
+                    break;
+                };
+
+                for (px, dst) in row.chunks_exact(samples).zip(canvas_row.iter_mut().skip(left)) {
+                    // gray and RGB have an alpha channel after them, if any
+                    let src = if samples % 2 == 0 { px[samples - 1] } else { 255 };
+
+                    *dst = match blend {
+                        BlendOp::Source => src,
+                        BlendOp::Over => src + ((*dst as u16 * (255 - src as u16) + 127) / 255) as u8,
+                    };
+                }
+            }
+
+            let transparent = canvas.iter().filter(|&&alpha| alpha < 255).count();
+
+            if shown == 0 {
+                probe.alpha_coverage = (!canvas.is_empty()).then(|| transparent as f64 / canvas.len() as f64);
+            }
+
+            shown += 1;
+
+            if transparent > 0 {
+                probe.alpha = true;
+                break;
+            }
+
+            match (dispose, previous) {
+                (DisposeOp::Background, _) => {
+                    for row in canvas.chunks_exact_mut(width).skip(top).take(frame_height) {
+                        row.iter_mut().skip(left).take(frame_width).for_each(|alpha| *alpha = 0);
+                    }
+                }
+                (DisposeOp::Previous, Some(previous)) => canvas = previous,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(probe)
+}
+
+/// What's read straight from the chunks, without decoding anything
+#[derive(Default)]
+struct Container {
+    /// From `acTL`, where 0 means forever, `None` for a still image
+    num_plays: Option<u32>,
+    /// Delay of each animation frame from its `fcTL`, in centiseconds
+    delays: Vec<u16>,
+    /// `IDAT` comes before any `fcTL`, so the default image isn't part of the animation
+    hidden_default_image: bool,
+    /// An alpha channel, a `tRNS` chunk or a frame disposed of to the background could show transparent pixels
+    maybe_transparent: bool,
+    has_color_profile: bool,
+    has_xmp: bool,
+}
+
+impl Container {
+    /// The decoder has already validated the header, so this only reads what's there.
+    fn parse(data: &[u8]) -> Self {
+        let mut container = Container::default();
+        let mut seen_idat = false;
+
+        for (kind, payload) in chunks(data.get(SIGNATURE.len()..).unwrap_or_default()) {
+            match kind {
+                // gray with alpha, or RGBA
+                b"IHDR" if payload.len() >= 13 => container.maybe_transparent |= matches!(payload[9], 4 | 6),
+                b"tRNS" => container.maybe_transparent = true,
+                b"acTL" if payload.len() >= 8 => {
+                    container.num_plays =
+                        Some(u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]));
+                }
+                // sequence number, size and offsets, then the delay as a fraction of a second, and the disposal
+                b"fcTL" if payload.len() >= 26 => {
+                    let numerator = u16::from_be_bytes([payload[20], payload[21]]) as u32;
+                    // a denominator of 0 means centiseconds
+                    let denominator = match u16::from_be_bytes([payload[22], payload[23]]) {
+                        0 => 100,
+                        denominator => denominator as u32,
+                    };
+
+                    let delay = (numerator * 100 + denominator / 2) / denominator;
+                    container.delays.push(delay.min(u16::MAX as u32) as u16);
+
+                    // disposing of the first frame to the previous canvas clears it too
+                    container.maybe_transparent |=
+                        payload[24] == 1 || (payload[24] == 2 && container.delays.len() == 1);
+                }
+                b"IDAT" if !seen_idat => {
+                    seen_idat = true;
+                    container.hidden_default_image = container.delays.is_empty();
+                }
+                b"iCCP" => container.has_color_profile = true,
+                b"iTXt" => container.has_xmp |= payload.starts_with(b"XML:com.adobe.xmp\0"),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        container
+    }
+}
+
+/// Chunks as their type and payload, skipping their CRCs, stopping at a truncated one
+fn chunks(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let (header, rest) = (data.get(..8)?, &data[8..]);
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let payload = rest.get(..size)?;
+
+        data = rest.get(size + 4..).unwrap_or_default();
+
+        Some((&header[4..], payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANIMATED: &[u8] = include_bytes!("../tests/fixtures/animated.png");
+
+    #[test]
+    fn animated() {
+        // 4x4 RGBA frames, the default image opaque for 5/100 s, then half transparent for 1/10 s, looping forever
+        let probe = crate::probe(ANIMATED, &ProbeOptions::default()).unwrap();
+
+        assert_eq!((probe.width, probe.height), (4, 4));
+        assert_eq!(probe.frames, 2);
+        assert_eq!(probe.duration, 15);
+        assert_eq!(probe.loop_count, Some(0));
+        assert!(probe.alpha);
+        assert_eq!(probe.alpha_coverage, Some(0.0));
+    }
+
+    #[test]
+    fn chunks_stop_at_a_truncated_one() {
+        // cut off in the second frame's `fcTL`
+        let second = ANIMATED.windows(4).rposition(|kind| kind == b"fcTL").unwrap();
+        let container = Container::parse(&ANIMATED[..second + 12]);
+
+        assert_eq!(container.num_plays, Some(0));
+        assert_eq!(container.delays, [5]);
+        assert!(!container.hidden_default_image);
+        assert!(container.maybe_transparent);
+    }
+}
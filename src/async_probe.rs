@@ -0,0 +1,112 @@
+//! Probing a tokio [`AsyncRead`] for the `tokio` feature, for services that receive uploads on a runtime.
+//!
+//! The decoder only reads synchronously, so the probe runs on tokio's blocking thread pool, fed chunks as they
+//! arrive through a bounded channel. Only a few chunks are ever held, however large the input is.
+
+use std::{
+    future::{poll_fn, Future},
+    io::{self, Read},
+    pin::{pin, Pin},
+    task::Poll,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+};
+
+use crate::{probe, GifProbe, ProbeError, ProbeOptions};
+
+/// Bytes read from the input at a time
+const CHUNK_SIZE: usize = 64 << 10;
+
+/// Chunks read ahead of the probe before waiting for it to catch up
+const CHUNKS_AHEAD: usize = 4;
+
+/// Same as [`probe`], reading `reader` without blocking the task, and returning once the probe is done, which
+/// can be before the end of the input, when nothing asks for the rest of it.
+///
+/// Must be called from within a tokio runtime. Dropping the future ends the input where it was, so the probe
+/// left running on the blocking pool fails quickly instead of waiting for more.
+pub async fn probe_async<R: AsyncRead + Unpin>(reader: R, options: &ProbeOptions) -> Result<GifProbe, ProbeError> {
+    let (sender, receiver) = mpsc::channel(CHUNKS_AHEAD);
+
+    let options = options.clone();
+    let mut probing = tokio::task::spawn_blocking(move || {
+        let chunks = Chunks {
+            receiver,
+            chunk: Vec::new(),
+            pos: 0,
+        };
+
+        probe(chunks, &options)
+    });
+
+    let mut feed = pin!(feed(reader, sender));
+    let mut fed = false;
+
+    // the probe can finish before the input does, and then the input isn't waited on
+    let result = poll_fn(|cx| {
+        if let Poll::Ready(result) = Pin::new(&mut probing).poll(cx) {
+            return Poll::Ready(result);
+        }
+
+        if !fed {
+            fed = feed.as_mut().poll(cx).is_ready();
+        }
+
+        Poll::Pending
+    })
+    .await;
+
+    result.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Sends chunks of `reader` until the end of it, an error, or the probe no longer wanting any more.
+/// An empty chunk marks the end.
+async fn feed<R: AsyncRead + Unpin>(mut reader: R, sender: mpsc::Sender<io::Result<Vec<u8>>>) {
+    loop {
+        let mut chunk = vec![0; CHUNK_SIZE];
+
+        let result = reader.read(&mut chunk).await.map(|n| {
+            chunk.truncate(n);
+            chunk
+        });
+
+        let end = !matches!(result, Ok(ref chunk) if !chunk.is_empty());
+
+        if sender.send(result).await.is_err() || end {
+            return;
+        }
+    }
+}
+
+/// The receiving end of [`feed`] as a blocking [`Read`]
+struct Chunks {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    /// How much of `chunk` has been read
+    pos: usize,
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(Ok(chunk)) if !chunk.is_empty() => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Some(Err(e)) => return Err(e),
+                // the end of the input, or the future was dropped
+                Some(Ok(_)) | None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
@@ -0,0 +1,215 @@
+//! AVIF image and image sequence probing for the `avif` feature, filling in the same summary fields as for a GIF.
+//!
+//! Everything comes from the ISOBMFF boxes without decoding any AV1 data, so `alpha` only says whether there's
+//! an alpha plane, not whether any pixel in it is actually transparent.
+
+use crate::{
+    probe::{Codec, GifProbe, STILL},
+    ProbeError, ProbeOptions,
+};
+
+/// Auxiliary image types that carry an alpha plane, from MIAF and the older HEIF spelling
+const ALPHA_URNS: [&[u8]; 2] = [
+    b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha",
+    b"urn:mpeg:hevc:2015:auxid:1",
+];
+
+/// Whether the start of the input is an `ftyp` box with an AVIF image or image sequence brand
+pub fn sniff(magic: &[u8]) -> bool {
+    magic.len() >= 12 && magic[4..8] == *b"ftyp" && matches!(&magic[8..12], b"avif" | b"avis")
+}
+
+pub fn probe(data: &[u8], options: &ProbeOptions) -> Result<GifProbe, ProbeError> {
+    let item = Item::parse(data);
+    let tracks: Vec<Track> = find(data, b"moov").map_or(Vec::new(), |moov| {
+        boxes(moov)
+            .filter(|&(kind, _)| kind == b"trak")
+            .filter_map(|(_, trak)| Track::parse(trak))
+            .collect()
+    });
+
+    // the color track of a sequence, where the alpha plane is an auxiliary track
+    let sequence = tracks.iter().find(|track| track.handler == *b"pict");
+
+    let (width, height) = sequence
+        .map(|track| track.size)
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .or(item.as_ref().map(|item| item.size))
+        .ok_or(ProbeError::Avif)?;
+
+    if matches!(options.max_pixels, Some(m) if m < (width as u64 * height as u64)) {
+        return Err(ProbeError::TooLarge);
+    }
+
+    // the output has GIF's 16-bit dimensions
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(ProbeError::TooLarge);
+    };
+
+    let mut probe = GifProbe {
+        codec: Codec::Av1,
+        width,
+        height,
+        alpha: match sequence {
+            Some(_) => tracks.iter().any(|track| track.handler == *b"auxv"),
+            None => item.as_ref().is_some_and(|item| item.alpha),
+        },
+        ..GifProbe::default()
+    };
+
+    match sequence {
+        Some(track) => probe.add_delays(track.delays.iter().map(|&(count, delay)| (count as u64, delay))),
+        None => probe.add_delays(STILL),
+    }
+
+    Ok(probe)
+}
+
+/// The still image described by the `meta` box's item properties
+struct Item {
+    /// The largest `ispe`, since a thumbnail item may be smaller than the primary image
+    size: (u32, u32),
+    /// An `auxC` property declares an alpha plane
+    alpha: bool,
+}
+
+impl Item {
+    fn parse(data: &[u8]) -> Option<Self> {
+        // `meta` is a full box, with 4 bytes of version and flags before its children
+        let meta = find(data, b"meta")?.get(4..)?;
+        let ipco = find(find(meta, b"iprp")?, b"ipco")?;
+
+        let size = boxes(ipco)
+            .filter(|&(kind, _)| kind == b"ispe")
+            .filter_map(|(_, ispe)| Some((u32_at(ispe, 4)?, u32_at(ispe, 8)?)))
+            .max_by_key(|&(width, height)| width as u64 * height as u64)?;
+
+        let alpha = boxes(ipco).any(|(kind, auxc)| {
+            kind == b"auxC" && ALPHA_URNS.iter().any(|urn| auxc.get(4..).is_some_and(|aux| aux.starts_with(urn)))
+        });
+
+        Some(Item { size, alpha })
+    }
+}
+
+/// A `trak` of an image sequence
+struct Track {
+    /// `pict` for the color images, `auxv` for the alpha plane
+    handler: [u8; 4],
+    /// From `tkhd`
+    size: (u32, u32),
+    /// Frame counts and their delays in centiseconds, from `stts`
+    delays: Vec<(u32, u16)>,
+}
+
+impl Track {
+    fn parse(trak: &[u8]) -> Option<Self> {
+        // width and height are the last fields, as 16.16 fixed point, whatever the version
+        let tkhd = find(trak, b"tkhd")?;
+        let at = tkhd.len().checked_sub(8)?;
+        let size = (u32_at(tkhd, at)? >> 16, u32_at(tkhd, at + 4)? >> 16);
+
+        let mdia = find(trak, b"mdia")?;
+        let handler = find(mdia, b"hdlr")?.get(8..12)?.try_into().ok()?;
+
+        // version 1 has 64-bit creation and modification times before the timescale
+        let mdhd = find(mdia, b"mdhd")?;
+        let timescale = u32_at(mdhd, if *mdhd.first()? == 1 { 20 } else { 12 })?.max(1) as u64;
+
+        let stts = find(find(find(mdia, b"minf")?, b"stbl")?, b"stts")?;
+        let delays = (0..u32_at(stts, 4)? as usize)
+            .map(|i| {
+                let (count, delta) = (u32_at(stts, 8 + i * 8)?, u32_at(stts, 12 + i * 8)? as u64);
+                let delay = (delta * 100 + timescale / 2) / timescale;
+
+                Some((count, delay.min(u16::MAX as u64) as u16))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Track { handler, size, delays })
+    }
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The payload of the first box of this type
+fn find<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data).find(|&(k, _)| k == kind).map(|(_, payload)| payload)
+}
+
+/// Boxes as their type and payload, stopping at a truncated one
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let kind = data.get(4..8)?;
+
+        let (header, size) = match u32_at(data, 0)? {
+            // the size is in a 64-bit field after the type
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+            // the box extends to the end
+            0 => (8, data.len() as u64),
+            size => (8, size as u64),
+        };
+
+        let size = usize::try_from(size).ok()?;
+        let payload = data.get(header..size)?;
+
+        data = &data[size..];
+
+        Some((kind, payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STILL: &[u8] = include_bytes!("../tests/fixtures/still.avif");
+    const SEQUENCE: &[u8] = include_bytes!("../tests/fixtures/sequence.avif");
+
+    #[test]
+    fn still() {
+        // a 16x12 thumbnail's `ispe` before the 64x48 image's, inside an `ipco` with a 64-bit size
+        let probe = crate::probe(STILL, &ProbeOptions::default()).unwrap();
+
+        assert_eq!((probe.width, probe.height), (64, 48));
+        assert_eq!(probe.frames, 1);
+        assert_eq!(probe.duration, 0);
+        assert_eq!(probe.loop_count, None);
+        assert!(probe.alpha);
+        assert_eq!(probe.bytes_read, STILL.len() as u64);
+    }
+
+    #[test]
+    fn sequence() {
+        // three frames of 40 ms and one of 500 ms at a timescale of 1000, with an alpha track
+        let probe = crate::probe(SEQUENCE, &ProbeOptions::default()).unwrap();
+
+        assert_eq!((probe.width, probe.height), (32, 24));
+        assert_eq!(probe.frames, 4);
+        assert_eq!(probe.duration, 62);
+        assert_eq!((probe.min_delay, probe.max_delay), (Some(4), Some(50)));
+        assert_eq!(probe.loop_count, None);
+        assert!(probe.alpha);
+    }
+
+    #[test]
+    fn boxes_stop_at_a_truncated_one() {
+        let mut data = Vec::new();
+        data.extend(b"\x00\x00\x00\x0cfree\x01\x02\x03\x04");
+        data.extend(b"\x00\x00\x00\x01skip\x00\x00\x00\x00\x00\x00\x00\x12\x05\x06");
+        // claims 32 bytes with only 12 there
+        data.extend(b"\x00\x00\x00\x20mdat\x07\x08\x09\x0a");
+
+        let found: Vec<_> = boxes(&data).collect();
+        assert_eq!(
+            found,
+            [(&b"free"[..], &b"\x01\x02\x03\x04"[..]), (b"skip", b"\x05\x06")]
+        );
+
+        // a size of 0 reaches the end
+        let found: Vec<_> = boxes(b"\x00\x00\x00\x00mdat\x01\x02\x03").collect();
+        assert_eq!(found, [(&b"mdat"[..], &b"\x01\x02\x03"[..])]);
+    }
+}
@@ -0,0 +1,181 @@
+//! Probing many inputs at once, with each failure isolated to its own result.
+
+use std::{
+    io::Read,
+    sync::{mpsc, Arc, Mutex},
+};
+
+use crate::{probe, GifProbe, ProbeError, ProbeOptions};
+
+/// Probes each reader in turn, pairing every result with the caller's key for that input.
+///
+/// A failing input only fails its own entry, the rest are still probed.
+pub fn probe_many<K, R, I>(inputs: I, options: &ProbeOptions) -> Vec<(K, Result<GifProbe, ProbeError>)>
+where
+    I: IntoIterator<Item = (K, R)>,
+    R: Read,
+{
+    inputs.into_iter().map(|(key, reader)| (key, probe(reader, options))).collect()
+}
+
+/// Same as [`probe_many`], but spread across up to `threads` threads. Results keep the order of the inputs.
+///
+/// Inputs are pulled from the iterator as threads become free, so it can open files lazily.
+pub fn probe_many_parallel<K, R, I>(
+    inputs: I,
+    options: &ProbeOptions,
+    threads: usize,
+) -> Vec<(K, Result<GifProbe, ProbeError>)>
+where
+    I: IntoIterator<Item = (K, R)>,
+    I::IntoIter: Send,
+    K: Send,
+    R: Read,
+{
+    let inputs = Mutex::new(inputs.into_iter().enumerate());
+    let results = Mutex::new(Vec::new());
+
+    std::thread::scope(|s| {
+        for _ in 0..threads.max(1) {
+            s.spawn(|| loop {
+                let Some((i, (key, reader))) = inputs.lock().unwrap().next() else {
+                    break;
+                };
+
+                let result = probe(reader, options);
+
+                results.lock().unwrap().push((i, key, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|&(i, ..)| i);
+    results.into_iter().map(|(_, key, result)| (key, result)).collect()
+}
+
+/// Same as [`probe_many_parallel`], but sends each result as soon as its input is done, in no particular order,
+/// rather than collecting them all first.
+///
+/// At most `threads` finished results wait in the channel, after which the workers block until they're received.
+/// Dropping the receiver stops the workers after their current input.
+pub fn probe_stream<K, R, I>(
+    inputs: I,
+    options: ProbeOptions,
+    threads: usize,
+) -> mpsc::Receiver<(K, Result<GifProbe, ProbeError>)>
+where
+    I: IntoIterator<Item = (K, R)>,
+    I::IntoIter: Send + 'static,
+    K: Send + 'static,
+    R: Read,
+{
+    let inputs = Arc::new(Mutex::new(inputs.into_iter()));
+    let options = Arc::new(options);
+    let (tx, rx) = mpsc::sync_channel(threads.max(1));
+
+    for _ in 0..threads.max(1) {
+        let (inputs, options, tx) = (inputs.clone(), options.clone(), tx.clone());
+
+        std::thread::spawn(move || loop {
+            let Some((key, reader)) = inputs.lock().unwrap().next() else {
+                break;
+            };
+
+            if tx.send((key, probe(reader, &options))).is_err() {
+                break;
+            }
+        });
+    }
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JsonOptions;
+
+    const TWO_FRAMES: &[u8] = include_bytes!("../tests/fixtures/two_frames.gif");
+
+    /// Valid GIFs at even keys, inputs that aren't GIFs or are cut off at odd ones
+    fn inputs() -> Vec<(usize, &'static [u8])> {
+        (0..9)
+            .map(|key| match key % 4 {
+                0 | 2 => (key, TWO_FRAMES),
+                1 => (key, &b"not a gif"[..]),
+                _ => (key, &TWO_FRAMES[..20]),
+            })
+            .collect()
+    }
+
+    fn summary(results: &[(usize, Result<GifProbe, ProbeError>)]) -> Vec<(usize, Result<String, String>)> {
+        results
+            .iter()
+            .map(|(key, result)| {
+                let result = match result {
+                    Ok(probe) => Ok(probe.to_json(&JsonOptions::default())),
+                    Err(e) => Err(e.to_string()),
+                };
+
+                (*key, result)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn many_isolates_failures() {
+        let results = probe_many(inputs(), &ProbeOptions::default());
+
+        assert_eq!(
+            results.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            (0..9).collect::<Vec<_>>()
+        );
+
+        for (key, result) in &results {
+            match key % 2 {
+                0 => assert_eq!(result.as_ref().unwrap().frames, 2, "input {key}"),
+                _ => assert!(result.is_err(), "input {key}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let options = ProbeOptions::default();
+        let sequential = summary(&probe_many(inputs(), &options));
+
+        for threads in [0, 1, 3, 16] {
+            assert_eq!(
+                summary(&probe_many_parallel(inputs(), &options, threads)),
+                sequential,
+                "{threads} threads"
+            );
+        }
+    }
+
+    #[test]
+    fn stream_sends_every_result_once() {
+        use std::{sync::mpsc::RecvTimeoutError, time::Duration};
+
+        let options = ProbeOptions::default();
+        let sequential = summary(&probe_many(inputs(), &options));
+
+        // with one thread, the channel holds a single result, so the worker has to wait on the receiver
+        for threads in [1, 3, 16] {
+            let rx = probe_stream(inputs(), options.clone(), threads);
+            let mut results = Vec::new();
+
+            loop {
+                match rx.recv_timeout(Duration::from_secs(10)) {
+                    Ok(result) => results.push(result),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => panic!("{threads} threads stalled"),
+                }
+            }
+
+            results.sort_by_key(|&(key, _)| key);
+            assert_eq!(summary(&results), sequential, "{threads} threads");
+        }
+    }
+}
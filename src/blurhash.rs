@@ -0,0 +1,166 @@
+//! [BlurHash](https://blurha.sh) of the first frame for `--blurhash`.
+
+use std::f64::consts::PI;
+
+use gif::Frame;
+
+use crate::composite::Canvas;
+
+/// Horizontal and vertical components, the common choice for a roughly square placeholder
+const COMPONENTS: (usize, usize) = (4, 3);
+
+/// The canvas is sampled down to at most this many pixels along each side, which is plenty for so few components
+const MAX_SAMPLES: usize = 64;
+
+const BASE83: &[u8; 83] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Draws the first frame onto a canvas the size of the logical screen and encodes it as a BlurHash,
+/// with transparent pixels treated as black. `None` if the canvas is empty.
+pub fn encode(width: u16, height: u16, frame: &Frame, global_palette: Option<&[u8]>) -> Option<String> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    canvas.draw(frame, global_palette);
+
+    let (width, height) = (width as usize, height as usize);
+    let (sw, sh) = (width.min(MAX_SAMPLES), height.min(MAX_SAMPLES));
+
+    // nearest sample of each point on the grid, in linear RGB and premultiplied by alpha
+    let mut samples = Vec::with_capacity(sw * sh);
+
+    for sy in 0..sh {
+        for sx in 0..sw {
+            let i = ((sy * height / sh) * width + sx * width / sw) * 4;
+            let px = &canvas.pixels()[i..i + 4];
+            let alpha = px[3] as f64 / 255.0;
+
+            samples.push([0, 1, 2].map(|c| srgb_to_linear(px[c]) * alpha));
+        }
+    }
+
+    let (cx, cy) = COMPONENTS;
+    let mut factors = Vec::with_capacity(cx * cy);
+
+    for j in 0..cy {
+        for i in 0..cx {
+            let normalization = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+
+            for (n, sample) in samples.iter().enumerate() {
+                let (x, y) = ((n % sw) as f64, (n / sw) as f64);
+                let basis =
+                    normalization * (PI * i as f64 * x / sw as f64).cos() * (PI * j as f64 * y / sh as f64).cos();
+
+                for (f, &s) in factor.iter_mut().zip(sample) {
+                    *f += basis * s;
+                }
+            }
+
+            factors.push(factor.map(|f| f / samples.len() as f64));
+        }
+    }
+
+    let (dc, ac) = factors.split_first().expect("at least one component");
+
+    let mut hash = String::with_capacity(4 + 2 * factors.len());
+
+    base83(&mut hash, (cx - 1 + (cy - 1) * 9) as u32, 1);
+
+    let max = match ac.iter().flatten().map(|f| f.abs()).reduce(f64::max) {
+        Some(actual) => {
+            let quantized = (actual * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+            base83(&mut hash, quantized, 1);
+            (quantized + 1) as f64 / 166.0
+        }
+        None => {
+            base83(&mut hash, 0, 1);
+            1.0
+        }
+    };
+
+    let [r, g, b] = dc.map(linear_to_srgb);
+    base83(&mut hash, (r as u32) << 16 | (g as u32) << 8 | b as u32, 4);
+
+    for factor in ac {
+        let [r, g, b] = factor.map(|f| {
+            let scaled = f / max;
+            (scaled.signum() * scaled.abs().sqrt() * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32
+        });
+
+        base83(&mut hash, r * 19 * 19 + g * 19 + b, 2);
+    }
+
+    Some(hash)
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let v = value as f64 / 255.0;
+
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+
+    if v <= 0.0031308 {
+        (v * 12.92 * 255.0 + 0.5) as u8
+    } else {
+        ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u8
+    }
+}
+
+/// Appends `value` as exactly `digits` base 83 digits, most significant first.
+fn base83(out: &mut String, value: u32, digits: u32) {
+    for i in (0..digits).rev() {
+        out.push(BASE83[(value / 83u32.pow(i) % 83) as usize] as char);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame covering the whole canvas, with `pixels` indexing into the global palette
+    fn frame(width: u16, height: u16, pixels: Vec<u8>) -> Frame<'static> {
+        Frame {
+            width,
+            height,
+            buffer: pixels.into(),
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn solid_color() {
+        let hash = encode(8, 8, &frame(8, 8, vec![0; 64]), Some(&[0x40, 0x80, 0xc0]));
+
+        // what the reference encoder gives for the same pixels
+        assert_eq!(hash.as_deref(), Some("LL7Wp@t:fQt:t:kDfQkDfQfQfQfQ"));
+    }
+
+    #[test]
+    fn blocks_of_color() {
+        // orange over the left 5 columns and green over the rest of the top half, then yellow over the left 3
+        // columns and blue over the rest of the bottom half. The AC value of largest magnitude is positive, so
+        // the reference encoders that quantise the maximum by value and by magnitude agree.
+        let palette = [0xe0, 0x60, 0x30, 0x20, 0xa0, 0x50, 0x30, 0x40, 0xc0, 0xf0, 0xe0, 0x90];
+        let pixels = (0..6)
+            .flat_map(|y| (0..8).map(move |x| if y < 3 { (x >= 5) as u8 } else { 3 - (x >= 3) as u8 }))
+            .collect();
+
+        let hash = encode(8, 6, &frame(8, 6, pixels), Some(&palette));
+
+        assert_eq!(hash.as_deref(), Some("L~Js^P},%EtQ,,vlNLSer:eYRoWV"));
+    }
+
+    #[test]
+    fn empty_canvas() {
+        assert_eq!(encode(0, 8, &frame(0, 8, Vec::new()), None), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Chained setters for [`ProbeOptions`], which the binary maps its flags onto one at a time.
+
+use std::{
+    num::NonZeroU64,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[cfg(feature = "png")]
+use std::path::PathBuf;
+
+#[cfg(feature = "png")]
+use crate::FramePosition;
+use crate::{ClassifyRules, ImageFormat, ProbeOptions, UiRules};
+
+/// Builds [`ProbeOptions`] one setting at a time, starting from the defaults, see [`ProbeOptions::builder`].
+///
+/// Limits and other optional settings take either a value or an `Option`, where `None` leaves them unset.
+///
+/// ```
+/// use gif_probe::ProbeOptions;
+///
+/// let options = ProbeOptions::builder().max_pixels(4096 * 4096).max_frames(None).comments(true).build();
+///
+/// assert_eq!(options.max_pixels, Some(4096 * 4096));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ProbeOptionsBuilder(ProbeOptions);
+
+/// A setter for each field, with `Option` fields taking anything that converts into one
+macro_rules! setters {
+    ($($(#[$attr:meta])* $name:ident: $kind:ident $ty:ty,)*) => {$(
+        $(#[$attr])*
+        #[doc = concat!("Sets [`ProbeOptions::", stringify!($name), "`]")]
+        pub fn $name(mut self, $name: setters!(@param $kind $ty)) -> Self {
+            self.0.$name = setters!(@value $kind $name);
+            self
+        }
+    )*};
+    (@param option $ty:ty) => { impl Into<Option<$ty>> };
+    (@param value $ty:ty) => { $ty };
+    (@value option $name:ident) => { $name.into() };
+    (@value value $name:ident) => { $name };
+}
+
+impl ProbeOptionsBuilder {
+    setters! {
+        max_duration: option u64,
+        max_frames: option NonZeroU64,
+        max_file_size: option u64,
+        max_pixels: option u64,
+        max_expansion: option f64,
+        max_memory: option NonZeroU64,
+        format_hint: option ImageFormat,
+        #[cfg(feature = "png")]
+        thumbnail: option PathBuf,
+        #[cfg(feature = "png")]
+        thumbnail_at: value FramePosition,
+        fps_timeline: value bool,
+        strict: value bool,
+        validate_canvas: value bool,
+        frame_offsets: value bool,
+        detail_frames: option u64,
+        drain: value bool,
+        frame_stats: value bool,
+        dominant_color: value bool,
+        average_color: value bool,
+        dominant_colors: option usize,
+        blurhash: value bool,
+        phash: value bool,
+        frame_at: option u64,
+        app_extensions: value bool,
+        comments: value bool,
+        xmp: value bool,
+        palette_stats: value bool,
+        count_used_colors: value bool,
+        max_simultaneous_colors: value bool,
+        grayscale: value bool,
+        lzw_stats: value bool,
+        explain_alpha: value bool,
+        exhaustive_alpha: value bool,
+        classify: option ClassifyRules,
+        #[cfg(feature = "hash")]
+        hash: value bool,
+        trailing_data: value bool,
+        detect_ui: option UiRules,
+        suggest_static: value bool,
+        timing: value bool,
+        cancel: option Arc<AtomicBool>,
+    }
+
+    pub fn build(self) -> ProbeOptions {
+        self.0
+    }
+}
+
+impl ProbeOptions {
+    /// A [`ProbeOptionsBuilder`] starting from the defaults
+    pub fn builder() -> ProbeOptionsBuilder {
+        ProbeOptionsBuilder::default()
+    }
+}
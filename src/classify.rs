@@ -0,0 +1,182 @@
+//! Heuristic content classification for `--classify`, from metrics the probe collects anyway.
+
+use gif::Frame;
+use serde::Serialize;
+
+use crate::GifProbe;
+
+/// Thresholds for [`ProbeOptions::classify`](crate::ProbeOptions::classify), see the README for how they combine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassifyRules {
+    /// A recording has at least this many frames
+    pub recording_min_frames: u64,
+    /// A recording has at least this fraction of frames that don't cover the whole canvas
+    pub recording_min_partial_ratio: f64,
+    /// A recording has at least this many colors in its largest palette
+    pub recording_min_colors: u16,
+    /// A recording has a steady frame rate, with delays varying by at most this
+    /// fraction of the mean (their coefficient of variation)
+    pub recording_max_delay_variation: f64,
+    /// Recording signals needed to classify as a recording
+    pub recording_min_score: u8,
+
+    /// An animation has at most this many frames
+    pub animation_max_frames: u64,
+    /// An animation has at most this many colors in its largest palette
+    pub animation_max_colors: u16,
+    /// Animation signals needed to classify as an animation
+    pub animation_min_score: u8,
+}
+
+impl Default for ClassifyRules {
+    fn default() -> Self {
+        ClassifyRules {
+            recording_min_frames: 50,
+            recording_min_partial_ratio: 0.5,
+            recording_min_colors: 128,
+            recording_max_delay_variation: 0.1,
+            recording_min_score: 3,
+            animation_max_frames: 30,
+            animation_max_colors: 64,
+            animation_min_score: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentClass {
+    Recording,
+    Animation,
+    Static,
+    Unknown,
+}
+
+impl ContentClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentClass::Recording => "recording",
+            ContentClass::Animation => "animation",
+            ContentClass::Static => "static",
+            ContentClass::Unknown => "unknown",
+        }
+    }
+}
+
+/// Result of [`ProbeOptions::classify`](crate::ProbeOptions::classify), with the scores behind it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Classification {
+    #[serde(skip)]
+    pub content_class: ContentClass,
+    /// Number of recording signals met, out of 4
+    #[serde(rename = "recording")]
+    pub recording_score: u8,
+    /// Number of animation signals met, out of 2
+    #[serde(rename = "animation")]
+    pub animation_score: u8,
+    /// Fraction of frames that don't cover the whole canvas
+    pub partial_frame_ratio: f64,
+    /// Coefficient of variation of the frame delays, 0 when perfectly steady
+    pub delay_variation: f64,
+}
+
+/// Accumulates the per-frame metrics, the rest come from the finished [`GifProbe`].
+#[derive(Default)]
+pub struct Classifier {
+    partial_frames: u64,
+    delay_sum: f64,
+    delay_sq_sum: f64,
+}
+
+impl Classifier {
+    pub fn add_frame(&mut self, frame: &Frame, full: bool) {
+        self.partial_frames += !full as u64;
+        self.delay_sum += frame.delay as f64;
+        self.delay_sq_sum += frame.delay as f64 * frame.delay as f64;
+    }
+
+    pub fn finish(self, probe: &GifProbe, rules: &ClassifyRules) -> Classification {
+        let frames = probe.frames.max(1) as f64;
+
+        let partial_frame_ratio = self.partial_frames as f64 / frames;
+
+        let mean = self.delay_sum / frames;
+        let variance = (self.delay_sq_sum / frames - mean * mean).max(0.0);
+        let delay_variation = if mean > 0.0 { variance.sqrt() / mean } else { 0.0 };
+
+        let recording_score = (probe.frames >= rules.recording_min_frames) as u8
+            + (partial_frame_ratio >= rules.recording_min_partial_ratio) as u8
+            + (probe.max_colors >= rules.recording_min_colors) as u8
+            + (delay_variation <= rules.recording_max_delay_variation) as u8;
+
+        let animation_score = (probe.frames <= rules.animation_max_frames) as u8
+            + (probe.max_colors <= rules.animation_max_colors) as u8;
+
+        let content_class = if probe.frames <= 1 {
+            ContentClass::Static
+        } else if recording_score >= rules.recording_min_score {
+            ContentClass::Recording
+        } else if animation_score >= rules.animation_min_score {
+            ContentClass::Animation
+        } else {
+            ContentClass::Unknown
+        };
+
+        Classification {
+            content_class,
+            recording_score,
+            animation_score,
+            partial_frame_ratio,
+            delay_variation,
+        }
+    }
+}
+
+/// Thresholds for [`ProbeOptions::detect_ui`](crate::ProbeOptions::detect_ui), all of which must be met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UiRules {
+    /// Neither side of the canvas is larger than this
+    pub max_size: u16,
+    /// The largest palette has at most this many colors
+    pub max_colors: u16,
+    /// At most this many frames
+    pub max_frames: u64,
+}
+
+impl Default for UiRules {
+    fn default() -> Self {
+        UiRules {
+            max_size: 128,
+            max_colors: 32,
+            max_frames: 60,
+        }
+    }
+}
+
+/// Result of [`ProbeOptions::detect_ui`](crate::ProbeOptions::detect_ui), with the factors behind it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UiDetection {
+    /// Likely a looping UI asset like a spinner or progress bar, when every factor holds
+    #[serde(skip)]
+    pub ui_animation: bool,
+    pub small: bool,
+    pub few_colors: bool,
+    pub few_frames: bool,
+    pub infinite_loop: bool,
+}
+
+impl UiDetection {
+    pub fn new(probe: &GifProbe, loop_count: Option<u16>, rules: &UiRules) -> Self {
+        let small = probe.width <= rules.max_size && probe.height <= rules.max_size;
+        let few_colors = probe.max_colors <= rules.max_colors;
+        let few_frames = probe.frames > 1 && probe.frames <= rules.max_frames;
+        let infinite_loop = loop_count == Some(0);
+
+        UiDetection {
+            ui_animation: small && few_colors && few_frames && infinite_loop,
+            small,
+            few_colors,
+            few_frames,
+            infinite_loop,
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Color and palette analyses, mostly over fully decoded frames.
+
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use gif::Frame;
+
+use crate::composite::Canvas;
+
+/// Set of distinct RGB colors, as a bitset over all 2^24 of them.
+pub struct ColorSet {
+    bits: Vec<u64>,
+    len: u32,
+}
+
+impl ColorSet {
+    pub fn new() -> Self {
+        ColorSet {
+            bits: vec![0; (1 << 24) / 64],
+            len: 0,
+        }
+    }
+
+    fn position(rgb: &[u8]) -> (usize, u64) {
+        let color = (rgb[0] as usize) << 16 | (rgb[1] as usize) << 8 | rgb[2] as usize;
+        (color / 64, 1 << (color % 64))
+    }
+
+    pub fn insert(&mut self, rgb: &[u8]) {
+        let (word, bit) = Self::position(rgb);
+
+        if self.bits[word] & bit == 0 {
+            self.bits[word] |= bit;
+            self.len += 1;
+        }
+    }
+
+    /// Counts the distinct colors of the opaque pixels in an RGBA buffer, leaving the set as it was.
+    /// The set must start out empty.
+    fn count_rgba(&mut self, pixels: &[u8]) -> u32 {
+        let opaque = || pixels.chunks_exact(4).filter(|px| px[3] != 0);
+
+        opaque().for_each(|px| self.insert(px));
+
+        let count = std::mem::take(&mut self.len);
+
+        // cheaper than clearing all 2 MiB of bits for each frame
+        for px in opaque() {
+            let (word, bit) = Self::position(px);
+            self.bits[word] &= !bit;
+        }
+
+        count
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Adds every color drawn by a frame, skipping its transparent pixels.
+    pub fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        let palette = frame.palette.as_deref().or(global_palette).unwrap_or(&[]);
+
+        // indices are only 256 possible values, so resolve each one once
+        let mut seen = [false; 256];
+
+        for &index in frame.buffer.iter() {
+            if std::mem::replace(&mut seen[index as usize], true) || frame.transparent == Some(index) {
+                continue;
+            }
+
+            let i = index as usize * 3;
+
+            if let Some(rgb) = palette.get(i..i + 3) {
+                self.insert(rgb);
+            }
+        }
+    }
+}
+
+/// Only every Nth pixel is looked at by [`dominant_colors`], odd so rows don't line up with each other.
+pub const DOMINANT_COLOR_STRIDE: usize = 7;
+
+/// Most frequent opaque RGB value among a sample of a frame's pixels, `None` if none are opaque.
+pub fn dominant_color(frame: &Frame, global_palette: Option<&[u8]>) -> Option<[u8; 3]> {
+    dominant_colors(frame, global_palette, 1).into_iter().next()
+}
+
+/// Up to `limit` of the most frequent opaque RGB values among a sample of a frame's pixels, most frequent first.
+pub fn dominant_colors(frame: &Frame, global_palette: Option<&[u8]>, limit: usize) -> Vec<[u8; 3]> {
+    let palette = frame.palette.as_deref().or(global_palette).unwrap_or(&[]);
+
+    let mut counts = [0u32; 256];
+
+    for &index in frame.buffer.iter().step_by(DOMINANT_COLOR_STRIDE) {
+        counts[index as usize] += 1;
+    }
+
+    if let Some(tr) = frame.transparent {
+        counts[tr as usize] = 0;
+    }
+
+    // palettes can repeat colors, so tally by the color rather than by its index
+    let mut tally: Vec<([u8; 3], u32)> = Vec::new();
+
+    for (index, &count) in counts.iter().enumerate().filter(|(_, &count)| count > 0) {
+        let Some(&[r, g, b]) = palette.get(index * 3..index * 3 + 3) else {
+            continue;
+        };
+
+        match tally.iter_mut().find(|(rgb, _)| *rgb == [r, g, b]) {
+            Some((_, total)) => *total += count,
+            None => tally.push(([r, g, b], count)),
+        }
+    }
+
+    // stable, so ties go to the lowest palette index
+    tally.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    tally.into_iter().take(limit).map(|(rgb, _)| rgb).collect()
+}
+
+/// Mean RGB value of a frame's opaque pixels, `None` if none are opaque.
+pub fn average_color(frame: &Frame, global_palette: Option<&[u8]>) -> Option<[u8; 3]> {
+    let palette = frame.palette.as_deref().or(global_palette).unwrap_or(&[]);
+
+    let mut counts = [0u64; 256];
+
+    for &index in frame.buffer.iter() {
+        counts[index as usize] += 1;
+    }
+
+    if let Some(tr) = frame.transparent {
+        counts[tr as usize] = 0;
+    }
+
+    let mut sums = [0u64; 3];
+    let mut pixels = 0;
+
+    for (rgb, &count) in palette.chunks_exact(3).zip(counts.iter()) {
+        for (sum, &channel) in sums.iter_mut().zip(rgb) {
+            *sum += channel as u64 * count;
+        }
+
+        pixels += count;
+    }
+
+    (pixels > 0).then(|| sums.map(|sum| ((sum + pixels / 2) / pixels) as u8))
+}
+
+/// Largest number of distinct colors visible on the composited canvas at once, after any frame.
+pub struct SimultaneousColors {
+    canvas: Canvas,
+    set: ColorSet,
+    max: u32,
+}
+
+impl SimultaneousColors {
+    pub fn new(width: u16, height: u16) -> Self {
+        SimultaneousColors {
+            canvas: Canvas::new(width, height),
+            set: ColorSet::new(),
+            max: 0,
+        }
+    }
+
+    pub fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        self.canvas.draw(frame, global_palette);
+        self.max = self.max.max(self.set.count_rgba(self.canvas.pixels()));
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+}
+
+/// Whether every color drawn by any frame is a shade of gray, with equal red, green and blue.
+pub struct GrayscaleCheck {
+    grayscale: bool,
+}
+
+impl GrayscaleCheck {
+    pub fn new() -> Self {
+        GrayscaleCheck { grayscale: true }
+    }
+
+    /// Checks the palette entries a frame draws with, skipping its transparent pixels.
+    pub fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        if !self.grayscale {
+            return;
+        }
+
+        let palette = frame.palette.as_deref().or(global_palette).unwrap_or(&[]);
+
+        let mut seen = [false; 256];
+
+        for &index in frame.buffer.iter() {
+            if std::mem::replace(&mut seen[index as usize], true) || frame.transparent == Some(index) {
+                continue;
+            }
+
+            let i = index as usize * 3;
+
+            if let Some(&[r, g, b]) = palette.get(i..i + 3) {
+                if r != g || g != b {
+                    self.grayscale = false;
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn is_grayscale(&self) -> bool {
+        self.grayscale
+    }
+}
+
+/// Result of [`ProbeOptions::palette_stats`](crate::ProbeOptions::palette_stats)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteStats {
+    /// Frames whose palette, local or global, differs from the previous frame's
+    pub palette_changes: u64,
+    /// Distinct palettes used by frames
+    pub distinct_palettes: u64,
+}
+
+/// Tracks the palette each frame uses, which only needs frame headers.
+#[derive(Default)]
+pub struct PaletteTracker {
+    last: Option<u64>,
+    seen: HashSet<u64>,
+    changes: u64,
+}
+
+impl PaletteTracker {
+    pub fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        let mut hasher = DefaultHasher::new();
+        frame.palette.as_deref().or(global_palette).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if matches!(self.last.replace(hash), Some(last) if last != hash) {
+            self.changes += 1;
+        }
+
+        self.seen.insert(hash);
+    }
+
+    pub fn finish(self) -> PaletteStats {
+        PaletteStats {
+            palette_changes: self.changes,
+            distinct_palettes: self.seen.len() as u64,
+        }
+    }
+}
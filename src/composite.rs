@@ -0,0 +1,114 @@
+//! Exact compositing of frames onto a canvas, the way a player would render them.
+//!
+//! This requires decoding every frame in full, so it's only used by the analyses that need it.
+
+use gif::{DisposalMethod, Frame};
+
+/// RGBA canvas the size of the logical screen, starting out fully transparent.
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+
+    /// Disposal of the last drawn frame, applied right before the next one is drawn
+    dispose: Option<(DisposalMethod, Rect)>,
+    /// Canvas before the last frame was drawn, if that frame is disposed with [`DisposalMethod::Previous`]
+    previous: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Copy)]
+struct Rect {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Canvas {
+    pub fn new(width: u16, height: u16) -> Self {
+        let (width, height) = (width as usize, height as usize);
+
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+            dispose: None,
+            previous: None,
+        }
+    }
+
+    /// RGBA pixels, row-major
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Disposes of the previously drawn frame, then draws a fully decoded, indexed `frame` on top.
+    pub fn draw(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        match self.dispose.take() {
+            Some((DisposalMethod::Background, rect)) => self.clear(rect),
+            Some((DisposalMethod::Previous, _)) => {
+                if let Some(previous) = self.previous.take() {
+                    self.pixels = previous;
+                }
+            }
+            _ => {}
+        }
+
+        let rect = Rect {
+            left: frame.left as usize,
+            top: frame.top as usize,
+            width: frame.width as usize,
+            height: frame.height as usize,
+        };
+
+        if frame.dispose == DisposalMethod::Previous {
+            self.previous = Some(self.pixels.clone());
+        }
+
+        self.dispose = Some((frame.dispose, rect));
+
+        let palette = frame.palette.as_deref().or(global_palette).unwrap_or(&[]);
+
+        for (y, row) in frame.buffer.chunks_exact(rect.width.max(1)).enumerate() {
+            let cy = rect.top + y;
+
+            if cy >= self.height {
+                break;
+            }
+
+            for (x, &index) in row.iter().enumerate() {
+                let cx = rect.left + x;
+
+                if cx >= self.width {
+                    break;
+                }
+
+                if frame.transparent == Some(index) {
+                    continue;
+                }
+
+                let i = index as usize * 3;
+
+                if let Some(rgb) = palette.get(i..i + 3) {
+                    let o = (cy * self.width + cx) * 4;
+                    self.pixels[o..o + 3].copy_from_slice(rgb);
+                    self.pixels[o + 3] = 255;
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self, rect: Rect) {
+        let right = (rect.left + rect.width).min(self.width);
+        let bottom = (rect.top + rect.height).min(self.height);
+
+        if rect.left >= right {
+            return;
+        }
+
+        for y in rect.top..bottom {
+            let row = y * self.width * 4;
+            self.pixels[row + rect.left * 4..row + right * 4].fill(0);
+        }
+    }
+}
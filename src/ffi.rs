@@ -0,0 +1,179 @@
+//! C interface for the `ffi` feature, exported from the `cdylib`.
+//!
+//! `include/gif_probe.h` declares it, generated by `cbindgen` with the settings in `cbindgen.toml`.
+//! Every call is independent and thread-safe. Strings handed out are owned by the library until given back to
+//! [`gif_probe_result_free`], and nothing passed in is kept past the call.
+
+use std::{
+    ffi::{c_char, CString},
+    num::NonZeroU64,
+    ptr, slice,
+};
+
+use crate::{probe, server::response, JsonOptions, ProbeError, ProbeOptions};
+
+/// What a call returns, which is also why the probe failed, if it did
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GifProbeStatus {
+    Ok = 0,
+    /// `data` was `NULL` with a non-zero `len`, or `out` was `NULL`. Nothing is written to `out`.
+    InvalidArgument = 1,
+    /// The data isn't an image that could be decoded
+    Decode = 2,
+    /// The data is larger than `max_file_size`
+    FileTooLarge = 3,
+    /// The canvas has more pixels than `max_pixels`
+    TooLarge = 4,
+    /// Every frame's pixels add up to more than `max_expansion` per byte
+    TooExpansive = 5,
+}
+
+/// Limits for [`gif_probe_run_with`], where 0 means none, or the default for `max_memory`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GifProbeOptions {
+    /// Stop after this many centiseconds of animation, like `-j`
+    pub max_duration: u64,
+    /// Stop after this many frames, like `-f`
+    pub max_frames: u64,
+    /// Fail with [`GifProbeStatus::TooLarge`] for canvases with more pixels than this, like `-d`
+    pub max_pixels: u64,
+    /// Fail with [`GifProbeStatus::FileTooLarge`] for more bytes than this, like `--max-file-size`
+    pub max_file_size: u64,
+    /// Fail with [`GifProbeStatus::TooExpansive`] past this ratio, like `--max-expansion`
+    pub max_expansion: f64,
+    /// Don't let the decoder allocate more than this many bytes, 20 MiB by default, like `-m`
+    pub max_memory: u64,
+}
+
+/// A few of the summary fields, and the whole record as JSON
+#[repr(C)]
+#[derive(Debug)]
+pub struct GifProbeResult {
+    pub alpha: bool,
+    pub animated: bool,
+    pub width: u16,
+    pub height: u16,
+    pub frames: u64,
+    /// In centiseconds
+    pub duration: u64,
+    /// In centiseconds, with delays as browsers play them
+    pub duration_normalized: u64,
+    pub bytes_read: u64,
+    /// NUL-terminated JSON, the record the binary prints for the data, or an object with an `error` if probing
+    /// failed. Owned by the library until passed to [`gif_probe_result_free`].
+    pub json: *mut c_char,
+}
+
+/// Probes `len` bytes at `data` with the default options, writing the result to `out`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or may be `NULL` if `len` is 0, and `out` must point to a
+/// writable [`GifProbeResult`]. Whatever `out` held before is overwritten without being freed.
+#[no_mangle]
+pub unsafe extern "C" fn gif_probe_run(data: *const u8, len: usize, out: *mut GifProbeResult) -> GifProbeStatus {
+    gif_probe_run_with(data, len, ptr::null(), out)
+}
+
+/// Same as [`gif_probe_run`], with the limits in `options`, or the defaults if it's `NULL`.
+///
+/// # Safety
+///
+/// Same as for [`gif_probe_run`], and `options` must be `NULL` or point to a readable [`GifProbeOptions`].
+#[no_mangle]
+pub unsafe extern "C" fn gif_probe_run_with(
+    data: *const u8,
+    len: usize,
+    options: *const GifProbeOptions,
+    out: *mut GifProbeResult,
+) -> GifProbeStatus {
+    if out.is_null() || (data.is_null() && len > 0) {
+        return GifProbeStatus::InvalidArgument;
+    }
+
+    let data = match len {
+        0 => &[][..],
+        len => slice::from_raw_parts(data, len),
+    };
+
+    let options = options.as_ref().copied().unwrap_or_default();
+    let options = ProbeOptions {
+        max_duration: (options.max_duration > 0).then_some(options.max_duration),
+        max_frames: NonZeroU64::new(options.max_frames),
+        max_pixels: (options.max_pixels > 0).then_some(options.max_pixels),
+        max_file_size: (options.max_file_size > 0).then_some(options.max_file_size),
+        max_expansion: (options.max_expansion > 0.0).then_some(options.max_expansion),
+        max_memory: NonZeroU64::new(options.max_memory),
+        ..ProbeOptions::default()
+    };
+
+    let result = options.check_file_size(len as u64).and_then(|()| probe(data, &options));
+
+    let (status, mut summary) = match &result {
+        Ok(probe) => (
+            GifProbeStatus::Ok,
+            GifProbeResult {
+                alpha: probe.alpha,
+                animated: probe.animated(),
+                width: probe.width,
+                height: probe.height,
+                frames: probe.frames,
+                duration: probe.duration,
+                duration_normalized: probe.duration_normalized,
+                bytes_read: probe.bytes_read,
+                json: ptr::null_mut(),
+            },
+        ),
+        Err(e) => (status_of(e), GifProbeResult::empty()),
+    };
+
+    // JSON escapes control characters, so there's no NUL inside
+    summary.json = CString::new(response(result, &JsonOptions::default())).unwrap_or_default().into_raw();
+
+    out.write(summary);
+    status
+}
+
+/// Frees the JSON of a result filled in by [`gif_probe_run`] or [`gif_probe_run_with`], setting it to `NULL`.
+/// Does nothing if `result` or its JSON is `NULL`, so freeing twice is harmless.
+///
+/// # Safety
+///
+/// `result` must be `NULL` or point to a result written by this library, whose JSON hasn't been changed since.
+#[no_mangle]
+pub unsafe extern "C" fn gif_probe_result_free(result: *mut GifProbeResult) {
+    if let Some(result) = result.as_mut() {
+        if !result.json.is_null() {
+            drop(CString::from_raw(result.json));
+            result.json = ptr::null_mut();
+        }
+    }
+}
+
+impl GifProbeResult {
+    fn empty() -> Self {
+        GifProbeResult {
+            alpha: false,
+            animated: false,
+            width: 0,
+            height: 0,
+            frames: 0,
+            duration: 0,
+            duration_normalized: 0,
+            bytes_read: 0,
+            json: ptr::null_mut(),
+        }
+    }
+}
+
+fn status_of(e: &ProbeError) -> GifProbeStatus {
+    match e {
+        ProbeError::FileTooLarge => GifProbeStatus::FileTooLarge,
+        ProbeError::TooLarge => GifProbeStatus::TooLarge,
+        ProbeError::TooExpansive(_) => GifProbeStatus::TooExpansive,
+        // strict mode and thumbnails can't be asked for through here
+        _ => GifProbeStatus::Decode,
+    }
+}
@@ -0,0 +1,349 @@
+//! A minimal HTTP/1.1 server for `serve --http` with the `http` feature, answering `POST /probe` with the probe's
+//! JSON for the file in the request body.
+//!
+//! Only what that needs is understood: bodies need a `Content-Length`, so chunked requests are refused, and
+//! connections are kept alive between requests unless the client asks otherwise.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    probe,
+    server::{error_response, response, ConnectionLimit, IDLE_TIMEOUT},
+    JsonOptions, ProbeOptions,
+};
+
+/// Most bytes of the request line or of a single header line
+const MAX_LINE: u64 = 8 << 10;
+
+/// Most header lines in a request
+const MAX_HEADERS: usize = 100;
+
+/// Longest the accepting thread spends turning a connection away when all of them are in use
+const BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Answers HTTP requests on `addr`, each connection on its own thread, until accepting one fails.
+///
+/// `POST /probe` probes the request body and answers `200` with the probe's JSON, or `422` with an object with
+/// an `error` if probing failed. Bodies larger than `max_body_size` are answered with `413` before any of them is
+/// read, any other path with `404`, and any other method with `405`.
+///
+/// At most [`MAX_CONNECTIONS`](crate::server::MAX_CONNECTIONS) are handled at once, ones past that are answered
+/// with `503` and closed.
+pub fn serve_http<A: ToSocketAddrs>(
+    addr: A,
+    options: &ProbeOptions,
+    json_options: &JsonOptions,
+    max_body_size: u64,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let json_options = &JsonOptions {
+        pretty: false,
+        ..*json_options
+    };
+
+    let limit = &ConnectionLimit::default();
+
+    std::thread::scope(|s| loop {
+        let stream = listener.accept()?.0;
+
+        let Some(slot) = limit.try_acquire() else {
+            turn_away(&stream);
+            continue;
+        };
+
+        s.spawn(move || {
+            let _slot = slot;
+
+            // a client that hung up or went idle can't be answered anyway
+            let _ = connection(&stream, options, json_options, max_body_size);
+        });
+    })
+}
+
+/// Answers `503` and closes the connection, reading what the client already sent first, since closing with
+/// unread input resets the connection, which can lose the answer before the client reads it
+fn turn_away(mut stream: &TcpStream) {
+    let deadline = Instant::now() + BUSY_TIMEOUT;
+
+    // the answer fits in the socket's buffer, so this only waits on a client that stopped reading
+    let _ = stream.set_write_timeout(Some(BUSY_TIMEOUT));
+    let error = error_response(&"too many connections, try again later");
+
+    if write_response(stream, 503, "Retry-After: 1\r\n", &error, false).is_err() {
+        return;
+    }
+
+    let _ = stream.shutdown(Shutdown::Write);
+    let mut buffer = [0; 4096];
+
+    // until the client closes its side too, or time is up however slowly it keeps sending
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() || stream.set_read_timeout(Some(left)).is_err() {
+            return;
+        }
+
+        if matches!(stream.read(&mut buffer), Ok(0) | Err(_)) {
+            return;
+        }
+    }
+}
+
+/// Answers requests until the client closes the connection, or one leaves it unusable
+fn connection(
+    stream: &TcpStream,
+    options: &ProbeOptions,
+    json_options: &JsonOptions,
+    max_body_size: u64,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    stream.set_write_timeout(Some(IDLE_TIMEOUT))?;
+
+    let mut input = BufReader::new(stream);
+    let mut output = stream;
+
+    loop {
+        let request = match Request::read(&mut input) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return write_response(output, 400, "", &error_response(&e), false);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // the body isn't read for anything but a probe, so the connection can't be reused after those
+        let len = match (request.method.as_str(), request.path()) {
+            ("POST", "/probe") => match request.content_length {
+                Some(_) if request.chunked => None,
+                Some(len) if len > max_body_size => {
+                    let error = format!("body is larger than the maximum of {max_body_size} bytes");
+                    return write_response(output, 413, "", &error_response(&error), false);
+                }
+                len => len,
+            },
+            (_, "/probe") => {
+                return write_response(output, 405, "Allow: POST\r\n", &error_response(&"use POST"), false)
+            }
+            _ => return write_response(output, 404, "", &error_response(&"not found"), false),
+        };
+
+        let Some(len) = len else {
+            let error = "a body with a `Content-Length` is required, chunked bodies aren't supported";
+            return write_response(output, 411, "", &error_response(&error), false);
+        };
+
+        if request.expect_continue {
+            output.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        }
+
+        let mut body = (&mut input).take(len);
+        let result = probe(&mut body, options);
+
+        io::copy(&mut body, &mut io::sink())?;
+
+        // the client went away partway through the body
+        if body.limit() > 0 {
+            return Ok(());
+        }
+
+        let status = if result.is_ok() { 200 } else { 422 };
+        write_response(output, status, "", &response(result, json_options), request.keep_alive)?;
+
+        if !request.keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// What's used of a request's head
+struct Request {
+    method: String,
+    target: String,
+    content_length: Option<u64>,
+    /// Any `Transfer-Encoding`, which can only end in `chunked` for a request
+    chunked: bool,
+    /// `Expect: 100-continue`, where the client waits to be told to send the body
+    expect_continue: bool,
+    keep_alive: bool,
+}
+
+impl Request {
+    /// Reads the request line and headers, `None` if the connection is closed before another request.
+    /// A malformed request fails with [`io::ErrorKind::InvalidData`].
+    fn read<R: BufRead>(input: &mut R) -> io::Result<Option<Request>> {
+        // blank lines before a request are allowed, as leftovers of the one before
+        let request_line = loop {
+            match read_line(input)? {
+                None => return Ok(None),
+                Some(line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+
+        let mut parts = request_line.split(' ');
+
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("malformed request line"));
+        };
+
+        let mut request = Request {
+            method: method.to_owned(),
+            target: target.to_owned(),
+            content_length: None,
+            chunked: false,
+            expect_continue: false,
+            keep_alive: match version {
+                "HTTP/1.1" => true,
+                "HTTP/1.0" => false,
+                _ => return Err(invalid("unsupported HTTP version")),
+            },
+        };
+
+        for _ in 0..=MAX_HEADERS {
+            let line = read_line(input)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+
+            if line.is_empty() {
+                return Ok(Some(request));
+            }
+
+            let (name, value) = line.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+            let value = value.trim();
+
+            match &*name.to_ascii_lowercase() {
+                // which of several lengths a proxy in front went by can't be known, so none is trusted
+                "content-length" if request.content_length.is_some() => {
+                    return Err(invalid("more than one Content-Length"));
+                }
+                "content-length" => {
+                    request.content_length = Some(value.parse().map_err(|_| invalid("invalid Content-Length"))?);
+                }
+                "transfer-encoding" => request.chunked = true,
+                "expect" => request.expect_continue = value.eq_ignore_ascii_case("100-continue"),
+                "connection" => {
+                    for option in value.split(',').map(str::trim) {
+                        if option.eq_ignore_ascii_case("close") {
+                            request.keep_alive = false;
+                        } else if option.eq_ignore_ascii_case("keep-alive") {
+                            request.keep_alive = true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(invalid("too many headers"))
+    }
+
+    /// The target without its query
+    fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+}
+
+/// A line without its line ending, `None` at the end of the input
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if input.take(MAX_LINE).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    if !line.ends_with('\n') {
+        return Err(match line.len() as u64 {
+            MAX_LINE => invalid("line too long"),
+            _ => io::ErrorKind::UnexpectedEof.into(),
+        });
+    }
+
+    line.pop();
+
+    if line.ends_with('\r') {
+        line.pop();
+    }
+
+    Ok(Some(line))
+}
+
+fn invalid(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn write_response<W: Write>(
+    mut output: W,
+    status: u16,
+    headers: &str,
+    body: &str,
+    keep_alive: bool,
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        503 => "Service Unavailable",
+        _ => "Unprocessable Content",
+    };
+
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+
+    write!(
+        output,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: {connection}\r\n{headers}\r\n{body}",
+        body.len()
+    )?;
+
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_length_has_to_be_given_once() {
+        let read = |head: &str| Request::read(&mut head.as_bytes()).map(|request| request.unwrap().content_length);
+
+        assert_eq!(
+            read("POST /probe HTTP/1.1\r\nContent-Length: 5\r\n\r\n").unwrap(),
+            Some(5)
+        );
+
+        for head in [
+            "POST /probe HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\n",
+            "POST /probe HTTP/1.1\r\nContent-Length: 5\r\ncontent-length: 7\r\n\r\n",
+            "POST /probe HTTP/1.1\r\nContent-Length: 5, 7\r\n\r\n",
+        ] {
+            assert_eq!(read(head).unwrap_err().kind(), io::ErrorKind::InvalidData, "{head:?}");
+        }
+    }
+
+    #[test]
+    fn turned_away_clients_get_the_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        // already sent by the time the server answers, which closing without reading it would reset
+        client.write_all(b"POST /probe HTTP/1.1\r\nContent-Length: 4\r\n\r\nGIF8").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        turn_away(&listener.accept().unwrap().0);
+
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).unwrap();
+
+        assert!(answer.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{answer:?}");
+        assert!(answer.contains("Retry-After: 1\r\n"));
+    }
+}
@@ -0,0 +1,343 @@
+//! Probes a GIF to detect if it _actually_ has transparent pixels, and accumulates misc data while we're at it.
+//!
+//! This is the library behind the `gif_probe` binary, see the README for what is reported and why.
+//!
+//! ```no_run
+//! use gif_probe::{probe, JsonOptions, ProbeOptions};
+//!
+//! let file = std::io::BufReader::new(std::fs::File::open("file.gif").unwrap());
+//! let probe = probe(file, &ProbeOptions::default()).unwrap();
+//!
+//! println!("{}", probe.to_json(&JsonOptions::default()));
+//! ```
+
+use std::{
+    fmt,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "png")]
+use std::path::PathBuf;
+
+#[cfg(feature = "apng")]
+mod apng;
+#[cfg(feature = "tokio")]
+mod async_probe;
+#[cfg(feature = "avif")]
+mod avif;
+mod batch;
+mod blurhash;
+mod builder;
+mod classify;
+mod colors;
+mod composite;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "http")]
+mod http;
+mod output;
+mod phash;
+mod probe;
+mod probe_iter;
+#[cfg(feature = "python")]
+mod python;
+mod scan;
+mod server;
+
+#[cfg(feature = "png")]
+mod thumbnail;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(feature = "webp")]
+mod webp;
+
+pub use batch::{probe_many, probe_many_parallel, probe_stream};
+pub use builder::ProbeOptionsBuilder;
+pub use classify::{Classification, ClassifyRules, ContentClass, UiDetection, UiRules};
+pub use colors::PaletteStats;
+pub use output::{csv_error_record, error_record, rejected_record, DurationUnit, JsonOptions};
+pub use probe::{
+    probe, AlphaExplanation, CanvasCheck, Codec, FrameAt, FrameDetail, GifProbe, ImageFormat, PaletteSource,
+    StaticSuggestion, Timing, Version,
+};
+pub use probe_iter::ProbeIter;
+pub use scan::{AppExtension, Comment, Violation};
+pub use server::{serve_json_lines, serve_length_prefixed};
+
+#[cfg(unix)]
+pub use server::serve_unix_socket;
+
+#[cfg(feature = "http")]
+pub use http::serve_http;
+
+#[cfg(feature = "tokio")]
+pub use async_probe::probe_async;
+
+#[cfg(feature = "png")]
+pub use thumbnail::FramePosition;
+
+/// Limits and optional analyses for [`probe`]. The defaults match running the binary without flags, and
+/// [`builder`](Self::builder) sets them one at a time.
+#[derive(Debug, Default, Clone)]
+pub struct ProbeOptions {
+    /// Stop processing after this duration is reached, setting [`GifProbe::truncated`]
+    pub max_duration: Option<u64>,
+
+    /// Stop processing after this many frames, setting [`GifProbe::truncated`]
+    pub max_frames: Option<NonZeroU64>,
+
+    /// Fail as soon as more than this many bytes are read from the input, see also
+    /// [`check_file_size`](Self::check_file_size)
+    pub max_file_size: Option<u64>,
+
+    /// Fail if the canvas has more pixels than this
+    pub max_pixels: Option<u64>,
+
+    /// Fail if [`GifProbe::expansion_ratio`] is more than this
+    pub max_expansion: Option<f64>,
+
+    /// Don't decode if the decoder would allocate more than this (in bytes), 20 MiB if not given
+    pub max_memory: Option<NonZeroU64>,
+
+    /// Probe the input as this format instead of going by its first bytes
+    pub format_hint: Option<ImageFormat>,
+
+    /// Write the frame at [`thumbnail_at`](Self::thumbnail_at) to this path as a PNG
+    #[cfg(feature = "png")]
+    pub thumbnail: Option<PathBuf>,
+
+    /// Which frame [`thumbnail`](Self::thumbnail) writes over the frames before it, the first by default
+    #[cfg(feature = "png")]
+    pub thumbnail_at: FramePosition,
+
+    /// Collect [`GifProbe::fps_timeline`]
+    pub fps_timeline: bool,
+
+    /// Fail on structural problems the decoder would otherwise tolerate, see [`Violation`]
+    pub strict: bool,
+
+    /// Collect [`GifProbe::canvas`], reporting frames outside of the logical screen rather than failing on them
+    pub validate_canvas: bool,
+
+    /// Collect [`GifProbe::frame_offsets`]
+    pub frame_offsets: bool,
+
+    /// Collect [`GifProbe::frames_detail`] for up to this many frames
+    pub detail_frames: Option<u64>,
+
+    /// Read the rest of the input to EOF after probing, so [`GifProbe::bytes_read`] is the full input size
+    pub drain: bool,
+
+    /// Collect [`GifProbe::first_frame_bit_depth`], [`GifProbe::zero_delay_frames`]
+    /// and [`GifProbe::longest_frame`]
+    pub frame_stats: bool,
+
+    /// Collect [`GifProbe::dominant_color`]
+    pub dominant_color: bool,
+
+    /// Collect [`GifProbe::average_color`]
+    pub average_color: bool,
+
+    /// Collect [`GifProbe::dominant_colors`] with up to this many colors
+    pub dominant_colors: Option<usize>,
+
+    /// Collect [`GifProbe::blurhash`]
+    pub blurhash: bool,
+
+    /// Collect [`GifProbe::phash`]
+    pub phash: bool,
+
+    /// Collect [`GifProbe::frame_at`] for the frame at this index, and stop walking the file after it
+    pub frame_at: Option<u64>,
+
+    /// Collect [`GifProbe::app_extensions`]
+    pub app_extensions: bool,
+
+    /// Collect [`GifProbe::comments`]
+    pub comments: bool,
+
+    /// Collect [`GifProbe::xmp`]
+    pub xmp: bool,
+
+    /// Collect [`GifProbe::palette_stats`]
+    pub palette_stats: bool,
+
+    /// Collect [`GifProbe::used_colors`], which decodes every frame
+    pub count_used_colors: bool,
+
+    /// Collect [`GifProbe::max_simultaneous_colors`], which decodes and composites every frame
+    pub max_simultaneous_colors: bool,
+
+    /// Collect [`GifProbe::grayscale`], which decodes every frame
+    pub grayscale: bool,
+
+    /// Collect [`GifProbe::max_lzw_code_width`]
+    pub lzw_stats: bool,
+
+    /// Collect [`GifProbe::alpha_explanation`]
+    pub explain_alpha: bool,
+
+    /// Collect [`GifProbe::rendered_alpha`], which decodes and composites every frame
+    pub exhaustive_alpha: bool,
+
+    /// Collect [`GifProbe::classification`] using these rules
+    pub classify: Option<ClassifyRules>,
+
+    /// Collect [`GifProbe::sha256`], which reads the rest of the input to EOF like [`drain`](Self::drain)
+    #[cfg(feature = "hash")]
+    pub hash: bool,
+
+    /// Collect [`GifProbe::trailing_bytes`] and [`GifProbe::polyglot_suspect`], which reads the rest of the input
+    /// to EOF like [`drain`](Self::drain)
+    pub trailing_data: bool,
+
+    /// Collect [`GifProbe::ui_detection`] using these rules
+    pub detect_ui: Option<UiRules>,
+
+    /// Collect [`GifProbe::static_suggestion`], which decodes and composites every frame
+    pub suggest_static: bool,
+
+    /// Collect [`GifProbe::timing`]
+    pub timing: bool,
+
+    /// Fail with [`ProbeError::Cancelled`] once this is set, checked before each frame, so another thread can
+    /// abort a probe whose result is no longer wanted, like when the client that sent the file disconnects
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl ProbeOptions {
+    /// Fails like probing would if an input of `len` bytes is larger than [`max_file_size`](Self::max_file_size),
+    /// so a file whose size is known up front can be rejected without reading any of it.
+    pub fn check_file_size(&self, len: u64) -> Result<(), ProbeError> {
+        match self.max_file_size {
+            Some(max) if len > max => Err(ProbeError::FileTooLarge),
+            _ => Ok(()),
+        }
+    }
+
+    /// [`max_memory`](Self::max_memory), or 20 MiB if not given
+    pub(crate) fn memory_limit(&self) -> NonZeroU64 {
+        // SAFETY: Obviously non-zero
+        self.max_memory.unwrap_or(unsafe { NonZeroU64::new_unchecked(1024 * 1024 * 20) })
+    }
+
+    /// Fails with [`ProbeError::Cancelled`] if [`cancel`](Self::cancel) has been set
+    pub(crate) fn check_cancelled(&self) -> Result<(), ProbeError> {
+        match self.cancel {
+            Some(ref cancel) if cancel.load(Ordering::Relaxed) => Err(ProbeError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProbeError {
+    /// The GIF couldn't be decoded
+    Decode(gif::DecodingError),
+
+    /// The input is larger than [`ProbeOptions::max_file_size`]
+    FileTooLarge,
+
+    /// The canvas has more pixels than [`ProbeOptions::max_pixels`]
+    TooLarge,
+
+    /// [`GifProbe::expansion_ratio`] is more than [`ProbeOptions::max_expansion`], with the ratio
+    TooExpansive(f64),
+
+    /// [`ProbeOptions::strict`] found a structural problem
+    Strict(Violation),
+
+    /// [`ProbeOptions::cancel`] was set before probing finished
+    Cancelled,
+
+    /// Writing [`ProbeOptions::thumbnail`] failed
+    #[cfg(feature = "png")]
+    Thumbnail(std::io::Error),
+
+    /// The WebP couldn't be decoded
+    #[cfg(feature = "webp")]
+    WebP(image_webp::DecodingError),
+
+    /// The PNG couldn't be decoded
+    #[cfg(feature = "apng")]
+    Png(png::DecodingError),
+
+    /// The AVIF has neither an image sequence track nor an image size
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl ProbeError {
+    /// Which policy limit the input is over, as written to `rejected` by [`rejected_record`], or `None` if it
+    /// couldn't be probed for any other reason
+    pub fn rejection(&self) -> Option<&'static str> {
+        match self {
+            ProbeError::FileTooLarge => Some("file_too_large"),
+            ProbeError::TooLarge => Some("too_large"),
+            ProbeError::TooExpansive(_) => Some("too_expansive"),
+            _ => None,
+        }
+    }
+}
+
+impl From<gif::DecodingError> for ProbeError {
+    fn from(err: gif::DecodingError) -> Self {
+        ProbeError::Decode(err)
+    }
+}
+
+#[cfg(feature = "webp")]
+impl From<image_webp::DecodingError> for ProbeError {
+    fn from(err: image_webp::DecodingError) -> Self {
+        ProbeError::WebP(err)
+    }
+}
+
+#[cfg(feature = "apng")]
+impl From<png::DecodingError> for ProbeError {
+    fn from(err: png::DecodingError) -> Self {
+        ProbeError::Png(err)
+    }
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Decode(err) => write!(f, "error decoding the GIF: {err}"),
+            ProbeError::FileTooLarge => f.write_str("file too large"),
+            ProbeError::TooLarge => f.write_str("image too large"),
+            ProbeError::TooExpansive(ratio) => write!(f, "image expands to {ratio:.1} pixels per byte read"),
+            ProbeError::Strict(violation) => write!(f, "strict mode violation: {violation:?}"),
+            ProbeError::Cancelled => f.write_str("probing was cancelled"),
+            #[cfg(feature = "png")]
+            ProbeError::Thumbnail(err) => write!(f, "error writing the thumbnail: {err}"),
+            #[cfg(feature = "webp")]
+            ProbeError::WebP(err) => write!(f, "error decoding the WebP: {err}"),
+            #[cfg(feature = "apng")]
+            ProbeError::Png(err) => write!(f, "error decoding the PNG: {err}"),
+            #[cfg(feature = "avif")]
+            ProbeError::Avif => f.write_str("error reading the AVIF: no image sequence track or image size"),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProbeError::Decode(err) => Some(err),
+            #[cfg(feature = "png")]
+            ProbeError::Thumbnail(err) => Some(err),
+            #[cfg(feature = "webp")]
+            ProbeError::WebP(err) => Some(err),
+            #[cfg(feature = "apng")]
+            ProbeError::Png(err) => Some(err),
+            _ => None,
+        }
+    }
+}
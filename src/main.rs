@@ -10,6 +10,11 @@
  *
  * Therefore, it is not necessary to actually accumulate and dispose pixels values.
  *
+ * A cleared area usually gets painted right back by the next frame though, so a `Background` disposal only counts
+ * when the frame after it doesn't cover the whole area, or declares a transparent index and so might leave some of
+ * it showing through. Both are known from the frame headers, without decoding. A disposal with no frame after it,
+ * or none seen before probing stopped, still counts.
+ *
  * Note: This binary intentionally has bad error handling. It either succeeds or it doesn't.
  * Any file that fails to process should be considered invalid.
  *
@@ -17,6 +22,8 @@
  * ```
  * gif_probe
  *     [-l max_duration_in_ms]
+ *     [-f max_frames]
+ *     [-t timeout_ms]
  *     [-d max_pixels]
  *     [-m max_memory_in_bytes]
  *      -i path/file.gif
@@ -24,6 +31,207 @@
  *
  * Or pass `-i -` to read from stdin, which can be useful when spawning as a subprocess.
  *
+ * Everything below describes the `probe` subcommand, which is also what runs when no subcommand is given, so
+ * `gif_probe -i file.gif` and `gif_probe probe -i file.gif` are the same. The other subcommands are:
+ *
+ * - `gif_probe validate -i a.gif [-i b.gif ...]` probes each file like `--strict --validate-canvas`, printing an
+ *   error record like `--files-from` for each file that can't be opened, doesn't follow the GIF block structure,
+ *   has frames extending past the canvas or has no frame covering all of it. Valid files print nothing, and the
+ *   exit code is 5 if any file had a problem.
+ * - `gif_probe extract -i file.gif -o frame.png` writes the first frame as an RGBA PNG like `--thumbnail`, without
+ *   reading past it. It requires the `png` feature. `--frame <n>` writes the frame at 0-based index `n` instead, and
+ *   `--at-ms <t>` the frame showing `t` milliseconds into the animation, with delays as browsers play them like
+ *   `duration_normalized`. Either way the frames before it are composited underneath, handling disposal, so the PNG
+ *   matches what a player shows at that point. A timestamp past the end gets the last frame, while an index past the
+ *   last frame is an error.
+ * - `gif_probe serve` answers requests read from stdin like `--stdio-server` below, or with `--stdio` or `--socket`,
+ *   the length-prefixed payloads or socket connections described after it, or with `--http` and the `http` feature,
+ *   HTTP requests. Besides the `-j`, `-f`, `-d`, `-m`, `--max-file-size` and `--max-body-size` limits it only takes
+ *   `--float-precision` and `--normalize-output`.
+ *
+ * `gif_probe --help` lists the subcommands, and `gif_probe validate --help` and so on list their flags.
+ *
+ * Repeating `-i` probes each of the files in turn, e.g. `gif_probe -i a.gif -i b.gif`, and prints a single JSON
+ * array with one record per file, in the order given. Records have a leading `path` field like with
+ * `--files-from` below, and files that fail to open or probe get an error record instead of aborting the rest.
+ * With `--format oneline`, each file gets a line prefixed with its path instead. The `--expect-*` checks apply to
+ * every file as they do with `--files-from`.
+ *
+ * To probe many files, pass `--files-from list.txt` instead of `-i` to read newline-separated paths from a file,
+ * or `--files-from -` (or `--paths-from-stdin`) to read them from stdin, e.g.
+ * `find . -name '*.gif' | gif_probe --paths-from-stdin`. Each result is printed as one line of JSON with a
+ * leading `path` field, and files that fail to open or probe print `{"path": "...", "error": "..."}` instead of
+ * aborting the rest. Blank lines are skipped. With `--format oneline`, lines are prefixed with the path instead.
+ * `--jobs N` probes up to N files at once on as many threads, printing each line as soon as its file is done, so
+ * lines come out in the order files finish rather than the order of the list. Paths are read as they're needed, so
+ * a list being produced by `find` can be probed while it's still being written.
+ *
+ * `-i path/dir --recursive` walks a directory and its subdirectories instead, probing every `.gif` file (ignoring
+ * case) and printing lines like `--files-from`, with `path` relative to the directory so runs over the same tree can
+ * be diffed. `--glob PATTERN` probes files whose names match the pattern instead, where `*` matches any run of
+ * characters and `?` any single one, e.g. `--glob 'frame_??.gif'`. The pattern is case-sensitive and matches the
+ * file name only, not the directories leading to it. Files are probed in order of their paths, which keeps lines in
+ * a stable order unless `--jobs` is given. Symlinked directories aren't followed, and directories that can't be read
+ * print an error record to stderr and are skipped.
+ *
+ * `-o path/out.json` (or `--output`) writes the results to a file instead of stdout, in any of the modes above and
+ * any `--format`. They're written to a temporary file next to it and renamed over it once everything is written, so
+ * readers of that path only ever see a complete previous or new result, and stdout is left free for other output,
+ * like image bytes piped along by the same process. A run that fails partway leaves the old file untouched, but may
+ * leave its temporary file behind, named `.out.json.<pid>.tmp`. `--output` can't be combined with `--watch` or
+ * `--stdio-server`, neither of which finishes writing.
+ *
+ * To embed the probe as a long-lived co-process, `--stdio-server` answers requests read from stdin, one JSON object
+ * per line, until stdin closes. `{"path": "file.gif"}` probes a file and answers like `--files-from`, and `{"base64":
+ * "R0lGODlh..."}` probes the decoded bytes and answers with the plain probe JSON. Requests are handled one at a time
+ * in order, with each response line flushed before the next request is read. Requests that don't parse or fail to
+ * probe are answered with an `{"error": "..."}` line instead, and other fields in a request are ignored, whatever
+ * their type. The `--expect-*` checks don't apply, and the exit code is 0 once stdin
+ * closes.
+ *
+ * `gif_probe serve --stdio` instead reads the files themselves from stdin, each as its length in bytes as a 4-byte
+ * big-endian integer followed by that many bytes, and answers each with a line like a `base64` request, so a parent
+ * process can stream files to a single long-lived child without encoding them. Payloads are probed as they arrive
+ * rather than buffered, and any part probing didn't need is skipped to reach the next length. `--max-file-size`
+ * rejects a payload by its length before reading any of it. The exit code is 0 once stdin closes between payloads,
+ * and 1 if it closes in the middle of one, since nothing after it could be framed.
+ *
+ * On Unix, `gif_probe serve --socket /run/gif_probe.sock` listens on a Unix domain socket instead, so several
+ * services can share one sandboxed prober that outlives any of them. Each connection sends a file and shuts down its
+ * writing half, then gets a line like a `base64` request back before the connection is closed. Connections are probed
+ * concurrently, each on its own thread, up to 64 at once with the rest waiting to be accepted. A connection that
+ * sends or takes nothing for 60 seconds is answered with an error, or closed. The input is read to its end before
+ * answering, so a client can always finish writing it, except after `--max-file-size` rejects it. Binding fails if
+ * something already exists at the path, like the socket of an earlier run, which has to be removed first. Nothing is
+ * printed while serving, and the exit code is 1 if accepting a connection ever fails.
+ *
+ * The probe is also available as a library, see `gif_probe::probe` and `gif_probe::probe_many`, which probes many
+ * readers at once without one failure aborting the rest. `gif_probe::probe_stream` does the same on worker threads,
+ * returning a channel that yields each result as soon as it's done, so results can be forwarded incrementally without
+ * holding all of them in memory. A long probe can be aborted from another thread by setting the `AtomicBool` in
+ * `ProbeOptions::cancel`, such as when the client that sent the file disconnects, which is checked before each frame
+ * and fails the probe with `ProbeError::Cancelled`. To stop by rules of the caller's own, `gif_probe::ProbeIter`
+ * yields each frame's delay, disposal, position, size, transparent index and local palette size as the probe reaches
+ * it, walking no further than the frames taken, and `finish` returns the summary of those frames. Options can be set
+ * one at a time through `ProbeOptions::builder()`, whose setters are what the binary maps its flags onto. `GifProbe`
+ * implements `serde::Serialize` with the same fields as the default JSON output, to embed it in other serde output.
+ *
+ * Optional build features:
+ *
+ * - `png`: enables `--thumbnail path/thumb.png`, which writes the first frame as an RGBA PNG.
+ * - `hash`: enables `--hash sha256`, which adds a `sha256` field with the lowercase hex digest of the whole input.
+ *   The digest is computed from the same reads the decoder makes, but covering the whole input means reading the
+ *   rest of it to EOF after probing, same as `--drain-stdin`. Together with `--files-from`, `--key-by-hash`
+ *   prints a single JSON object mapping each file's digest to its result instead of one line per file, so files with
+ *   identical contents collapse into one entry. The first file in the list with given contents wins, so files are
+ *   probed one at a time and `--jobs` is refused, and files that fail to probe have no digest, so their error records
+ *   go to stderr instead.
+ * - `mmap`: enables `--mmap`, which memory-maps input files and probes straight from the mapping instead of copying
+ *   them through read calls, which helps with large files already in the page cache. It works anywhere `memmap2`
+ *   does, which includes Linux, macOS and Windows, but only for regular files, so it can't be combined with `-i -`.
+ *   The memory limit still applies to the decoder's allocations, but not to the mapping itself. Mapped files must not
+ *   be truncated or modified while being probed, which can crash the process (e.g. `SIGBUS`) or produce garbage.
+ * - `watch`: enables `--watch path/dir`, which keeps running until interrupted and probes each `.gif` file created or
+ *   modified in that directory, not including subdirectories. It prints one line per file like `--files-from`.
+ *   A file being written produces a burst of events, so a file is probed once it has had no events for 250ms.
+ *   Events come from the `notify` crate's recommended backend, which is inotify on Linux, FSEvents on macOS and
+ *   `ReadDirectoryChangesW` on Windows. Network filesystems and some containers don't deliver events at all,
+ *   and files written slower than the debounce interval may be probed before they're complete. Every line is
+ *   flushed as it's printed, so nothing is lost when interrupted.
+ * - `webp`: probes still and animated WebP files too, using the `image-webp` crate. Any input starting with a
+ *   `RIFF`/`WEBP` header is probed as a WebP instead of a GIF, with the same output fields. Frames, delays, the loop
+ *   count and whether there's an ICC profile or XMP come from the container's chunks, and the image data is only
+ *   decoded when the file declares an alpha channel, to find out whether any composited frame actually has
+ *   transparent pixels. WebP delays are milliseconds, so they're rounded to the nearest centisecond, and a still
+ *   image is a single frame with a delay of 0. Fields without a WebP equivalent, such as `version`, `max_colors` or
+ *   the `first_frame_*` fields, keep the values of a GIF without them. The optional analyses are left out, and
+ *   `--thumbnail` and `extract` fail with an error. The whole file is read into memory, since the decoder needs to
+ *   seek.
+ * - `apng`: probes still and animated PNG files too, using the `png` crate's decoder. Any input starting with the
+ *   PNG signature is probed as a PNG instead of a GIF, with the same output fields and the same caveats as `webp`.
+ *   Frames, delays and `loop_count` come from the `acTL` and `fcTL` chunks, where `loop_count` is the number of
+ *   plays with 0 meaning forever, like the GIF loop extension. A default image that isn't part of the animation
+ *   isn't counted as a frame. The image data is only decoded when a frame could show transparent pixels, because
+ *   there's an alpha channel or a `tRNS` chunk, or a frame is disposed of to the background. Then the frames are
+ *   composited like a player would, with their blend and dispose operations, and `alpha` is true once any of them
+ *   leaves a pixel less than fully opaque. PNGs larger than 65535 pixels on a side are rejected like `-d`.
+ * - `avif`: probes AVIF images and image sequences too, without pulling in a dependency. Any input starting with an
+ *   `ftyp` box branded `avif` or `avis` is probed as an AVIF instead of a GIF, with the same output fields and the
+ *   same caveats as `webp`. Everything comes from the ISOBMFF boxes without decoding any AV1 data, so `alpha` only
+ *   says whether there's an alpha plane, not whether any pixel in it is transparent. For a sequence, frames and
+ *   delays come from the color track's `stts` box, rounded to the nearest centisecond using its `mdhd` timescale,
+ *   the size comes from its `tkhd` box, and `alpha` is true when there's an auxiliary alpha track. A still image is
+ *   a single frame with a delay of 0, sized by its largest `ispe` property, with `alpha` true when it has an alpha
+ *   `auxC` property. AVIFs larger than 65535 pixels on a side are rejected like `-d`.
+ * - `msgpack` and `cbor`: enable `--format msgpack` and `--format cbor`, which write the same fields as the JSON
+ *   output as a single MessagePack or CBOR map, so a parent process can read results without parsing any text.
+ *   They're written by `rmp-serde` and `ciborium` from the same `serde` impls as the JSON. Fields keep their JSON
+ *   names and order, integers use the smallest encoding that fits, floats are 64-bit in MessagePack and as short as
+ *   they can be without losing precision in CBOR, and `--float-precision` and `--normalize-output` apply as they do
+ *   to JSON.
+ *   Like `--format binary-header`, these only describe a single file, so can't be combined with several inputs.
+ * - `http`: enables `gif_probe serve --http 127.0.0.1:8080`, a small HTTP/1.1 server for services that would rather
+ *   not manage a subprocess. `POST /probe` with the file as the body answers `200` with the probe JSON, or `422`
+ *   with a line like a `base64` request's error. Each connection gets its own thread and is kept alive between
+ *   requests unless the client asks otherwise, and one that sends or takes nothing for a minute is closed. Up to 64
+ *   connections are handled at once, and ones past that get `503` with `Retry-After: 1`. Bodies need a single
+ *   `Content-Length`, as chunked ones are refused with `411` and ones with several lengths with `400`, and ones
+ *   larger than `--max-body-size` (64 MiB by default) are refused with `413` before any of them is read. Other paths
+ *   get `404` and other methods `405`. It doesn't pull in a dependency, and has no TLS or authentication, so it's
+ *   meant to listen on a private address.
+ * - `ffi`: exports a C interface from the `cdylib` the crate also builds, `libgif_probe.so` or its platform's
+ *   equivalent, so C, C++ or Go services can probe in-process. `include/gif_probe.h` declares it, and is regenerated
+ *   with `cbindgen --config cbindgen.toml --output include/gif_probe.h`. `gif_probe_run(data, len, &result)` probes
+ *   `len` bytes with the default options, and `gif_probe_run_with` takes a `GifProbeOptions` of the `-j`, `-f`, `-d`,
+ *   `--max-file-size`, `--max-expansion` and `-m` limits, where 0 means none. Both return a `GifProbeStatus`, 0 on
+ *   success or a code for why probing failed, and fill in the `GifProbeResult` with a few summary fields plus `json`,
+ *   the full record or an `{"error": ...}` object. That string belongs to the library until passed to
+ *   `gif_probe_result_free`, while the input is only read during the call and never kept. Calls are independent, so
+ *   any number can run at once on different threads.
+ * - `wasm`: exports `probe(bytes)` to JavaScript through `wasm-bindgen`, for checking files in the browser before
+ *   they're uploaded, built with `wasm-pack build --features wasm`. It takes the whole file as a `Uint8Array` and
+ *   returns the same record as the JSON output as an object, or throws an `Error` saying why probing failed, with
+ *   the default options like the binary without flags. Only that is exported, since the library's file, socket and
+ *   thread based parts, while they compile for `wasm32-unknown-unknown`, can't work there.
+ * - `python`: a Python module through PyO3, for pipelines that would otherwise run the binary for every file, built
+ *   and installed with `pip install .`, which uses maturin and `pyproject.toml`. `gif_probe.probe(data)` probes the
+ *   whole file as `bytes`, and `gif_probe.probe_file(path)` the file at a path, both with the default options like
+ *   the binary without flags. Each returns the same record as the JSON output as a `dict`, or raises `ValueError`
+ *   saying why probing failed, or `OSError` when the file can't be opened. Other Python threads keep running while a
+ *   file is probed.
+ * - `tokio`: adds `gif_probe::probe_async`, which probes a tokio `AsyncRead` such as an upload still streaming in
+ *   from the network, for services that would otherwise have to block a runtime thread or buffer the whole body
+ *   first. The probe runs on tokio's blocking thread pool and is handed the input in 64 KiB chunks, a few at a time,
+ *   and the future resolves as soon as it's done, without waiting for the rest of the input unless something like
+ *   `drain` asks for it. Dropping the future ends the input where it was.
+ *
+ * Flags for features that weren't compiled in are still accepted, but exit with an error naming the missing feature.
+ *
+ * `--strict` additionally rejects files the decoder would otherwise tolerate:
+ *
+ * - graphic control, application and plain text extensions whose first sub-block isn't the size fixed by the spec
+ *   (4, 11 and 12 bytes), and graphic control extensions with more than one sub-block
+ * - LZW minimum code sizes outside of 2-8
+ * - streams that end inside a block, before its `0x00` terminator
+ * - streams that end without a trailer (`0x3B`)
+ * - frameless streams, with no image blocks at all
+ * - graphic control extensions in `GIF87a` files, which predate them
+ *
+ * A missing `0x00` sub-block terminator makes the next byte read as a block introducer, which the decoder already
+ * rejects either way, unless the stream ends there. When `--max-duration`, `--max-frames` or `--frame-at` stop before
+ * the trailer, only the frames walked are checked, up to the start of the last one's image data, however far past
+ * that the decoder happened to read ahead, and the trailer isn't required.
+ *
+ * `--expect-frames N`, `--expect-alpha true|false` and `--expect-dimensions WxH` turn the probe into an assertion
+ * for test scripts. The output is printed as usual, then each assertion that doesn't hold is reported on stderr and
+ * the process exits with code 3. With `--files-from`, every file is checked and the exit code is 3 if any of
+ * them failed an assertion. Files that fail to probe are reported as errors, not assertion failures.
+ * `--require-opaque-keyframe` similarly exits with code 4 unless the first frame alone makes a complete opaque
+ * image, meaning `first_frame_standalone` is true: the first frame starts at 0,0, spans the whole logical screen, and
+ * has no pixels with its transparent color index. When an `--expect-*` assertion fails as well, the exit code is 3.
+ *
+ * Exit code 1 is for usage errors, and a file that fails to probe in single-file mode still panics.
+ *
  * Example usage in PowerShell 7+:
  *
  * ```powershell
@@ -38,167 +246,1976 @@
  * ```json
  * {
  *   "alpha": false,
+ *   "alpha_coverage": 0.0,
  *   "max_colors": 256,
  *   "duration": 267,
+ *   "duration_unit": "cs",
+ *   "duration_normalized": 312,
  *   "frames": 40,
+ *   "min_delay": 5,
+ *   "max_delay": 50,
+ *   "mean_delay": 6.675,
+ *   "fps": 14.981,
+ *   "animated": true,
  *   "width": 480,
- *   "height": 270
+ *   "height": 270,
+ *   "format": "gif",
+ *   "codec": "lzw",
+ *   "version": "89a",
+ *   "background_index": 0,
+ *   "background_color": "#000000",
+ *   "pixel_aspect_ratio": null,
+ *   "first_frame_full": true,
+ *   "first_frame_standalone": true,
+ *   "first_frame_palette_source": "global",
+ *   "first_frame_dispose": "any",
+ *   "first_frame_transparent_index": null,
+ *   "any_later_transparent_index": false,
+ *   "safe_first_frame_preview": true,
+ *   "frameless": false,
+ *   "has_color_profile": false,
+ *   "loop_count": 0,
+ *   "has_xmp": false,
+ *   "bytes_read": 1398112,
+ *   "file_bytes_read": 1398112,
+ *   "memory_peak": 129600,
+ *   "expansion_ratio": 3.708
  * }
  * ```
+ *
+ * `alpha_coverage` is the fraction of the first frame's pixels with its transparent color, from 0 to 1, which
+ * tells mostly transparent sticker-style GIFs apart from ones with a few stray transparent pixels. It's `null`
+ * without frames, and only counts pixels within the first frame's own rectangle, so when that doesn't cover the
+ * whole canvas, see `first_frame_full`, the rest of the canvas is transparent too.
+ *
+ * `duration` is the sum of all frame delays, in centiseconds by default since that's the unit GIF stores them in,
+ * and `duration_unit` names the unit, see `--duration-unit` below. `duration_normalized` is how long the animation
+ * plays in browsers, which all treat delays of 0 or 1 centiseconds as 10, so it's what a viewer actually sees.
+ *
+ * `min_delay`, `max_delay` and `mean_delay` summarize the delays in the same unit, and `fps` is the average frame
+ * rate over the whole animation, `frames` per second of `duration`. They're `null` without frames, and `fps` also
+ * when every delay is zero. Delays are taken as encoded, not clamped like `duration_normalized`, and a wide gap
+ * between `min_delay` and `max_delay` points at timing a player may need to handle specially.
+ *
+ * `animated` is true when there are at least 2 frames and `duration` isn't zero. Frames that all have zero delays
+ * are drawn on top of each other at once as far as the file is concerned, so such a GIF is treated as a static
+ * image, even though browsers would play them 10 centiseconds apart.
+ *
+ * `format` is the container format the input was probed as, `"gif"`, or with the `webp`, `apng` and `avif` features
+ * `"webp"`, `"png"` and `"avif"`. It goes by the first bytes of the input, never by the file name, so it works the
+ * same on stdin. Input that starts like none of them is probed as a GIF and fails to decode. `--format-hint <format>`
+ * skips the sniffing and forces that decoder, failing if the input isn't actually in that format, and names of
+ * formats that need a feature this build lacks are rejected.
+ *
+ * `codec` is `"lzw"` for GIFs, the only compression GIF has. With the `webp` feature, WebPs report `"vp8"` for lossy
+ * and `"vp8l"` for lossless compression, going by the first frame when an animation mixes them. With the `apng`
+ * feature, PNGs report `"deflate"`, and with the `avif` feature, AVIFs report `"av1"`.
+ *
+ * `version` is the header's version, `"87a"` or `"89a"`. GIF87a predates extensions, so transparency, frame delays
+ * and looping all need 89a, and a graphic control extension in an 87a file gets the `version_block_conflict` warning.
+ * An 87a header mostly comes from old encoders, or ones like giflib that write it whenever no 89a feature is used.
+ *
+ * `background_index` is the logical screen's background color index, and `background_color` that entry of the
+ * global palette as `"#rrggbb"`, or `null` without a global palette or when the index is past its end. The spec has
+ * `"background"` disposal restore a frame's area to this color, though browsers clear it to transparent instead.
+ *
+ * `pixel_aspect_ratio` is the width of a pixel over its height, from the logical screen descriptor, which can
+ * express ratios from 1:4 to 4:1 in steps of 1/64. It's `null` when the file leaves it unspecified, meaning square
+ * pixels, as almost all files do. Browsers ignore it, but a faithful display scales the width by it.
+ *
+ * `first_frame_full` is true when the first frame covers the whole canvas, and `first_frame_standalone` when it
+ * additionally has no transparent pixels, so it can serve as a thumbnail without compositing later frames.
+ * `first_frame_palette_source` is `"local"` when the first frame brings its own palette, otherwise `"global"`,
+ * or `"none"` when there's no palette to speak of.
+ *
+ * `first_frame_dispose` is the first frame's disposal method, `"any"` (unspecified), `"keep"`, `"background"` or
+ * `"previous"`, and `null` without frames. `safe_first_frame_preview` is true when serving only the first frame is a
+ * faithful preview of how the animation starts: `first_frame_standalone` holds, and the first frame is disposed with
+ * `"any"` or `"keep"`, so it stays on the canvas as the base later frames draw over. With `"background"` or
+ * `"previous"` it's cleared away after its delay, and the animation really starts from an empty canvas.
+ *
+ * `first_frame_transparent_index` is the palette index the first frame declares transparent in its graphic control
+ * extension, whether or not any of its pixels use it, and `null` when it declares none. `any_later_transparent_index`
+ * is true when any frame after the first declares one, e.g. when deciding which palette slots re-encoding has to
+ * keep. Frames past where probing stopped aren't seen.
+ *
+ * `frameless` is true when the file has a valid header but no frames at all, just extensions and the trailer,
+ * which renders nothing. Such files otherwise look like a plain non-transparent GIF with `frames` being 0.
+ *
+ * `has_color_profile` is true when an application extension embeds color management data. The only identifier
+ * recognized is `ICCRGBG1` with auth code `012`, which carries an ICC profile. Only its identifier is read, the
+ * profile itself isn't kept. Extensions past where probing stopped aren't seen.
+ *
+ * `loop_count` is how many times the animation plays, from a `NETSCAPE2.0` or `ANIMEXTS1.0` application extension,
+ * where 0 means forever. It's `null` without such an extension, which most viewers take to mean playing once,
+ * though some browsers loop those forever too. Like the color profile, an extension past where probing stopped
+ * isn't seen.
+ *
+ * `has_xmp` is true when an `XMP Data` application extension with auth code `XMP` carries an XMP metadata packet,
+ * as written by Photoshop and others, usually with authorship and rights information. `--xmp` reports the packet
+ * itself, which isn't kept in memory otherwise.
+ *
+ * `warnings` lists conformance problems that were tolerated, and is omitted when there are none. Currently the only
+ * one is `"version_block_conflict"`, for a graphic control extension in a `GIF87a` file, which `--strict` rejects.
+ *
+ * `bytes_read` is how much of the input was consumed. Probing stops at the trailer, or earlier with `--max-duration`
+ * or `--max-frames`, and the decoder buffers ahead, so it isn't necessarily the size of the input. When reading from
+ * stdin, `--drain-stdin` reads and discards the rest of the stream to EOF after probing, so `bytes_read` is the full
+ * input size and the producer's pipe is drained instead of failing with `EPIPE`. That costs reading everything past
+ * the point probing stopped, which can be most of the file with `--max-duration`.
+ *
+ * `file_bytes_read` is how much of the file the frames walked cover, which doesn't depend on buffering: the offset
+ * just past the trailer when the whole stream was walked, or just past the last walked frame's image data when
+ * probing stopped early, like with `--max-frames`. A stream without a trailer reports `bytes_read`. PNGs, WebPs and
+ * AVIFs are read whole, so for them it's the size of the file.
+ *
+ * `memory_peak` is the largest frame buffer the decoder allocated, in bytes, which is what `-m` (20 MiB by default)
+ * limits. For a GIF that's one byte per pixel of the largest frame that needed decoding, and 0 when only frame
+ * headers were read, so its distribution over real files shows how much room a limit needs to leave. PNGs and WebPs
+ * report the buffer they're decoded into when checking them for transparency, which has to fit within `-m` too, along
+ * with a byte per pixel of the canvas for a PNG, or the image is rejected as too large before anything is allocated.
+ * AVIFs are never decoded.
+ *
+ * `expansion_ratio` is the number of pixels every frame adds up to at the size of the logical screen, `width` times
+ * `height` times `frames`, per byte of `bytes_read`. A tiny GIF that decodes into gigabytes of frames, a
+ * decompression bomb, has a ratio in the thousands, while ordinary GIFs rarely go past a few dozen. It only covers
+ * what was probed, so with `--max-duration` it's the ratio of the part walked. `--max-expansion <ratio>` fails the
+ * probe like `-d` does when the ratio is higher. PNGs, WebPs and AVIFs are read whole, so for them it's the size of
+ * the file.
+ *
+ * `--max-file-size <bytes>` fails the probe like `-d` does for inputs larger than that. A file is checked against its
+ * size before any of it is read, while stdin, whose size isn't known up front, fails as soon as reading it goes past
+ * the limit, so an oversized upload is never read in full. The same applies to every format, and to the paths and
+ * bytes of `serve` requests.
+ *
+ * `-t <ms>` (or `--timeout-ms`) is a hard bound on how long probing a single `-i` input may take, in wall-clock
+ * time. A watchdog thread exits the process with code 6 and an error on stderr once the budget runs out, since a
+ * crafted GIF can be slow to decode while staying within every other limit, and the decoder can't be interrupted.
+ * The budget covers opening and reading the input, including waiting on a slow producer over stdin, but not
+ * printing the result, which isn't started once the watchdog has fired. It can't be combined with several inputs,
+ * `--files-from` or `--recursive`, where exiting would lose every other file's result.
+ *
+ * An input over `-d`, `--max-file-size` or `--max-expansion` isn't invalid, just against policy, so it's reported
+ * as a result instead of failing like a broken file. A single input prints `{"rejected": ..., "error": ...}` and
+ * exits with code 7, where `rejected` is `"too_large"` for `-d`, `"file_too_large"` for `--max-file-size` or
+ * `"too_expansive"` for `--max-expansion`, and `error` is the same message a failure would get. Other formats than
+ * JSON print the message to stderr instead. With several inputs, and for `serve` requests, the file's error record
+ * gets the same `rejected` field between `path` and `error`. `-m` is enforced inside the decoder, which reports it
+ * like any other decoding error, so a file over it still fails as invalid.
+ *
+ * `--format oneline` prints a terse summary like `480x270 40f 267cs alpha=0 colors=256` instead,
+ * which is easier to scan and `grep` across many files. It's meant for people, not parsers, and may change.
+ *
+ * `--format binary-header` writes a fixed 32-byte record of the core fields instead, for storing results
+ * without parsing JSON. All integers are little-endian, and the 64-bit fields are 8-byte aligned:
+ *
+ * | Offset | Size | Field                        |
+ * |--------|------|------------------------------|
+ * | 0      | 4    | magic, `GIFP`                |
+ * | 4      | 2    | layout version, currently 1  |
+ * | 6      | 2    | flags, bit 0 is `alpha`      |
+ * | 8      | 2    | `max_colors`                 |
+ * | 10     | 2    | `width`                      |
+ * | 12     | 2    | `height`                     |
+ * | 14     | 2    | reserved, zero               |
+ * | 16     | 8    | `frames`                     |
+ * | 24     | 8    | `duration`                   |
+ *
+ * `--format headers` prints the core fields as `Key: Value` lines, one header per field, ready to be passed along
+ * as HTTP response headers:
+ *
+ * ```
+ * X-Gif-Alpha: false
+ * X-Gif-Max-Colors: 256
+ * X-Gif-Duration: 267
+ * X-Gif-Frames: 40
+ * X-Gif-Width: 480
+ * X-Gif-Height: 270
+ * ```
+ *
+ * Header names are `X-Gif-` followed by the JSON field name, with each word capitalized and joined by `-`.
+ * Values are plain ASCII numbers and `true`/`false`. Lines end with `\n` rather than `\r\n`.
+ *
+ * `--format csv` prints a header row and then a row of the core fields, for loading into a spreadsheet or database
+ * as-is. It also works with several `-i` inputs, `--files-from`, `--recursive` and `--watch`, where the header is
+ * printed once and each file gets a row:
+ *
+ * ```
+ * path,alpha,alpha_coverage,max_colors,duration,duration_unit,duration_normalized,frames,min_delay,max_delay,mean_delay,fps,animated,width,height,version,background_index,background_color,pixel_aspect_ratio,first_frame_full,first_frame_standalone,first_frame_palette_source,first_frame_dispose,first_frame_transparent_index,any_later_transparent_index,safe_first_frame_preview,frameless,has_color_profile,loop_count,has_xmp,bytes_read,error
+ * file.gif,false,0,256,267,cs,312,40,5,50,6.675,14.981,true,480,270,89a,0,#000000,,true,true,global,any,,false,true,false,false,0,false,1398112,
+ * ```
+ *
+ * Columns are named after the JSON fields, without `format`, `codec`, `file_bytes_read`, `memory_peak` and
+ * `expansion_ratio` and with a trailing `error` that is empty unless the file couldn't be probed, in which case only
+ * `path` and `error` are filled in. Fields that are `null` in the JSON are empty, and floats are rounded the same
+ * way. Fields with commas, quotes or newlines are quoted, doubling any quotes inside, and lines end with `\n`.
+ *
+ * Floating point fields are rounded to 3 decimal places by default, use `--float-precision N` to change that.
+ *
+ * `--duration-unit ms` or `--duration-unit s` converts `duration`, `duration_normalized`, `min_delay`, `max_delay`,
+ * `mean_delay` and `longest_frame_delay` from centiseconds, and sets `duration_unit` to match. Milliseconds stay
+ * integers, while seconds are floats, rounded like the others. The `delay` of a single frame, from `--detail-frames`,
+ * `--frame-at` or `--per-frame`, is the header field as encoded and stays in centiseconds, as does `--max-duration`.
+ * The binary header and `headers` formats are always in centiseconds too.
+ *
+ * JSON is printed on a single line by default, and `--pretty` indents it over several lines like the example above
+ * instead. It only applies to probing a single file, since the modes printing one result per line need each result
+ * to stay on its line. Fields are always written in the order documented here, see `--normalize-output` below for
+ * the full list, so the schema doesn't depend on anything but the flags given.
+ *
+ * `--per-frame` prints one line of JSON per frame before the usual output, for diagnosing files that play
+ * incorrectly, like `{"index": 0, "delay": 10, "dispose": "keep", "left": 0, "top": 0, "width": 480, ...}`. Each line
+ * has the frame's 0-based `index` and the same header fields as `--detail-frames`, for every frame walked, and the
+ * summary record that follows doesn't repeat them. Lines are printed once probing is done, and only when probing a
+ * single file.
+ *
+ * Optional fields, only present when requested:
+ *
+ * - `--frame-stats`: `first_frame_bit_depth`, the bits needed to index every color of the first frame's palette,
+ *   local or global, i.e. `ceil(log2(colors))` from 1 to 8. A 16-color palette is 4 bits, and 256 colors are 8.
+ *   This is also the smallest LZW minimum code size suited to the frame, except that LZW's minimum is 2.
+ *   Omitted when the first frame has no palette.
+ *   Also `zero_delay_frames`, the number of frames with a delay of 0, and `all_zero_delays`, true when there's more
+ *   than one frame and all of them have zero delays. That's a common export bug, which players render inconsistently,
+ *   either as a static image or as a flicker as fast as they can manage. Lastly `longest_frame_index` and
+ *   `longest_frame_delay`, the 0-based index and delay of the frame held the longest, which is often the most
+ *   representative frame of the animation and a good thumbnail candidate. Ties go to the first such frame, and both
+ *   are `null` without frames.
+ * - `--dominant-color`: `dominant_color`, the most frequent color of the first frame as `"#rrggbb"`, e.g. for a
+ *   placeholder background while the GIF loads. It's an approximation from every 7th pixel of the already decoded
+ *   first frame, resolved through its palette, rather than an exact count. Transparent pixels are skipped, and it's
+ *   omitted when none of the sampled pixels are opaque.
+ * - `--average-color`: `average_color`, the mean color of the first frame's pixels as `"#rrggbb"`, e.g. for a blurred
+ *   placeholder. Every pixel is resolved through the palette, with transparent pixels excluded from the average. When
+ *   the first frame is fully transparent, or there is no first frame, there's nothing to average, so it's `null`.
+ * - `--colors <n>`: `average_color` as above, plus `dominant_colors`, up to `n` of the first frame's most frequent
+ *   colors as `"#rrggbb"` strings, most frequent first. They're counted from the same sample as `dominant_color`,
+ *   and ties go to the lowest palette index, so the first entry always matches `dominant_color`. Fewer than `n`
+ *   colors are listed when the sample doesn't have that many, and none when it has no opaque pixels or there is no
+ *   first frame.
+ * - `--blurhash`: `blurhash`, a [BlurHash](https://blurha.sh) of the first frame with 4x3 components, for blurred
+ *   placeholders without decoding the GIF a second time. The frame is drawn onto the logical screen like a player
+ *   would, and sampled down to at most 64x64 pixels before encoding. BlurHash has no alpha, so transparent pixels
+ *   count as black. It's omitted without a first frame, or when the logical screen is empty.
+ * - `--phash`: `phash`, a 64-bit perceptual hash of the first frame as 16 lowercase hex digits, for deduplicating
+ *   re-uploads of the same GIF even when it was re-encoded. It's a difference hash: the frame is drawn onto the
+ *   logical screen, averaged down to a 9x8 grid of luma, and each bit is set when a cell is darker than its right
+ *   neighbor. Re-encoding, rescaling or requantizing the palette barely moves those averages, so copies of one image
+ *   hash the same or within a few bits, compare them by Hamming distance. Transparent pixels count as black like
+ *   `--blurhash`, and it's omitted in the same cases.
+ * - `--palette-stats`: `palette_changes` and `distinct_palettes`. Each frame uses its local palette if it has one,
+ *   otherwise the global palette. `palette_changes` counts frames whose palette bytes differ from the previous frame's,
+ *   capturing churn, while `distinct_palettes` counts how many different palettes are used at all. A high count of
+ *   changes suggests an encoder that isn't reusing the global palette. This only needs frame headers, not a decode.
+ * - `--count-used-colors`: `used_colors` and `palette_utilization`. This decodes every frame, so it's much slower.
+ *   `used_colors` is the number of distinct RGB values drawn across all frames, not counting transparent pixels, and
+ *   `palette_utilization` is `used_colors / max_colors`, or `null` when `max_colors` is 0. A low utilization means the
+ *   palette could be trimmed. With per-frame local palettes it can exceed 1, since `max_colors` is only the largest
+ *   single palette.
+ * - `--max-simultaneous-colors`: `max_simultaneous_colors`, the most distinct RGB values visible on the canvas at once.
+ *   Each frame is composited onto the canvas exactly like a player would, handling disposal, and the distinct colors of
+ *   its opaque pixels are counted after every frame. This is the most expensive analysis, costing a full decode and a
+ *   pass over every canvas pixel per frame, plus 2 MiB for the color set. It tells whether a GIF using per-frame local
+ *   palettes really shows more than 256 colors at once.
+ * - `--grayscale`: `grayscale`, true when every palette entry a frame draws with has equal red, green and blue.
+ *   Transparent pixels and unused palette entries don't count, so a grayscale image with a colorful palette is still
+ *   grayscale. Costs a full decode of every frame.
+ * - `--lzw-stats`: `max_lzw_code_width`, the widest LZW code read in any frame's image data, up to 12 bits,
+ *   which shows how far the dictionary grew before being cleared. The `gif` crate doesn't expose its decoder's state,
+ *   so this follows the code streams separately, only counting dictionary entries rather than decompressing. That's
+ *   cheap and needs no decode, but only covers frames that were walked. `null` when no frame had any image data.
+ * - `--trailing-data`: `trailing_bytes` and `polyglot_suspect`. `trailing_bytes` counts the bytes after the trailer,
+ *   which decoders ignore, and is 0 without a trailer. Uploads sometimes hide a second file there, making a polyglot
+ *   that's a valid GIF and, say, a valid ZIP at once. `polyglot_suspect` is true when those bytes contain the start
+ *   of a ZIP, RAR, 7z, PDF, ELF or shebang script, or an HTML, SVG, `<script` or PHP tag, ignoring ASCII case.
+ *   Anything else, like stray padding, only shows in `trailing_bytes`. This reads the input to EOF like
+ *   `--drain-stdin`, but matches signatures as the data streams by, so nothing past the trailer is kept in memory.
+ * - `--explain-alpha`: `alpha_explanation`, the first condition that made `alpha` true, as an object with a `method`,
+ *   the triggering `frame` index and a human-readable `reason`. `method` is `"first_frame_pixels"`, which also
+ *   reports the first frame's `transparent_pixels` count, `"background_disposal"` for a later frame disposed with
+ *   `Background` that the next frame doesn't repaint, or `"none"` with no `frame` when `alpha` is false.
+ * - `--exhaustive`: `rendered_alpha` and `rendered_alpha_frame`. This decodes and composites every frame, so it's
+ *   much slower. `alpha` only looks at frame headers, so a next frame that does repaint the cleared area but declares
+ *   a transparent index still counts, even if it never uses it. Instead, `rendered_alpha` is true when a composited
+ *   frame, as a browser shows it, has any fully transparent pixel, and `rendered_alpha_frame` is the index of the
+ *   first such frame, or `null` when there's none. Like elsewhere, disposal to the background clears to transparent.
+ * - `--fps-timeline`: `fps_timeline`, an array with the FPS implied by each frame's delay as encoded (`100 / delay`).
+ *   Zero-delay frames are `null`. Delays are not clamped the way browsers play them back.
+ * - `--validate-canvas`: `canvas_valid`, `canvas_overflow_frames` and `has_keyframe`. A frame overflows when its
+ *   left + width or top + height extends past the logical screen, and is a keyframe when it starts at 0,0 and spans
+ *   the whole logical screen. `canvas_valid` is false when any frame overflows or no frame is a keyframe.
+ *   Up to 16 overflowing frame indices are listed. Overflowing frames are normally a decode error,
+ *   so this also relaxes that check in order to report them.
+ * - `--frame-offsets`: `frame_offsets`, the byte offset of each frame's image descriptor (`0x2C`) from the start of
+ *   the input. A frame's graphic control extension, if any, comes shortly before it. Offsets are relative to
+ *   whatever was read, which is only useful for seeking when the input is a file.
+ * - `--detail-frames K`: `frames_detail`, the header fields (`delay`, `dispose`, `left`, `top`, `width`, `height`,
+ *   `transparent`, `local_colors` and `interlaced`) of the first K frames, plus `compressed_size`, the bytes each
+ *   frame's image data takes up from the LZW minimum code size through the block terminator. Unlike
+ *   `--max-duration`, which stops walking the file once reached, all frames are still counted towards `frames` and
+ *   `duration`.
+ * - `-f N` (or `--max-frames N`) and `-j` (or `--max-duration`): `truncated`, true when probing stopped after N
+ *   frames, or once `duration` reached the limit, with more frames left in the file. Either way walking the file
+ *   stops, so `frames`, `duration` and everything else only cover the frames before that. `--max-frames` also bounds
+ *   files padded with millions of zero-delay frames, which never reach a duration. The header of the next frame is
+ *   read to tell whether there was one, so `truncated` is false for a file that ends right at the limit. They only
+ *   apply to GIFs.
+ * - `--classify`: `content_class`, a heuristic guess of `"recording"`, `"animation"`, `"static"` or `"unknown"`,
+ *   and `content_scores` with the signals behind it. A single frame is `"static"`. Otherwise `recording` counts one
+ *   point each for at least 50 frames, at least half of the frames not covering the whole canvas
+ *   (`partial_frame_ratio`), at least 128 colors in the largest palette, and a steady frame rate, where the delays'
+ *   standard deviation is at most 10% of their mean (`delay_variation`). 3 or more points is a `"recording"`.
+ *   Failing that, `animation` counts a point each for at most 30 frames and at most 64 colors, and 2 points is
+ *   an `"animation"`. Anything else is `"unknown"`. The thresholds can be tuned through `gif_probe::ClassifyRules`
+ *   when using the library.
+ * - `--detect-ui`: `ui_animation` and `ui_factors`, flagging trivial looping UI assets like spinners and progress
+ *   bars. `ui_animation` is true when every factor holds: `small`, with neither side over 128 pixels, `few_colors`,
+ *   with at most 32 colors in the largest palette, `few_frames`, with 2 to 60 frames, and `infinite_loop`, where a
+ *   `NETSCAPE2.0` or `ANIMEXTS1.0` extension sets the loop count to 0, meaning forever. The thresholds can be changed
+ *   with `--ui-max-size`, `--ui-max-colors` and `--ui-max-frames`.
+ * - `--frame-at N`: `frame_at`, the header fields of frame N (0-based) like `--detail-frames`, plus its `index`, the
+ *   number of `colors` in the palette it uses and whether it has `transparent_pixels`. Frames before N are only walked
+ *   through their headers, frame N itself is decoded, and nothing after it is read, so `frames` and `duration` only
+ *   count up to frame N. `frame_at` is `null` when there are N or fewer frames.
+ * - `--dump-app-extensions`: `app_extensions`, every application extension in the order they appear, with its stream
+ *   `offset`, 8-byte `identifier` and 3-byte `auth_code`, and its `payload`, the data sub-blocks after the identifier
+ *   joined together without their size bytes. Payloads are base64-encoded to stay JSON-safe, e.g. the usual
+ *   `NETSCAPE2.0` loop extension has the payload `AQAA`, bytes `01 00 00` for looping forever. At most 1 MiB of each
+ *   extension is kept, and 4 MiB of all extensions and comments together, with `truncated` set if there was more.
+ *   Without this flag, only the extensions the summary reads are kept.
+ * - `--comments`: `comments`, every comment extension in the order they appear, with its stream `offset` and its
+ *   `text`, the data sub-blocks joined together. The spec calls for 7-bit ASCII, but text is read as UTF-8, which
+ *   many tools write, with invalid sequences replaced by `U+FFFD`. At most 64 KiB of each comment is kept, with
+ *   `truncated` set if there was more. Comments past where probing stopped aren't seen.
+ * - `--xmp`: `xmp`, the raw XMP packet from the extension `has_xmp` detects, usually an `<?xpacket?>` wrapped RDF/XML
+ *   document, with the magic trailer that follows it in the file removed. It's `null` without one. Like application
+ *   extensions, at most 1 MiB is kept, and less once other extensions and comments have used up their 4 MiB, so a
+ *   larger packet is cut off mid-document.
+ * - `--suggest-static`: `should_be_static`, `effectively_static` and `duplicate_frames`. This decodes and composites
+ *   every frame, so it's much slower. `effectively_static` is true when every composited frame is pixel-identical to
+ *   the first, and `duplicate_frames` counts frames identical to the one before them in position, palette and pixels.
+ *   `should_be_static` is true when the animation is effectively static, or more than 90% of frames after the first
+ *   are duplicates.
+ * - `--timing`: `decode_ms`, how long the whole probe took in wall-clock milliseconds, including reading the input to
+ *   EOF when something needs it, and `bytes_per_sec`, `bytes_read` over that time, which stands out for inputs
+ *   costing far more to decode than their size suggests. For a GIF, `header_ms`, `first_frame_ms` and
+ *   `remaining_frames_ms` split it into reading up to the global palette, reading the first frame, and walking the
+ *   frames after it. They're `null` for other formats, whose files are read whole.
+ *
+ * With `--normalize-output`, every optional field above is written regardless of the flags given, as `null` when it
+ * wasn't requested, and `warnings` is always an array. This way every record has the same keys in the same order,
+ * which suits loading results into a table. The full set of fields, in output order, is:
+ *
+ * `alpha`, `alpha_coverage`, `max_colors`, `duration`, `duration_unit`, `duration_normalized`, `frames`, `min_delay`,
+ * `max_delay`, `mean_delay`, `fps`, `animated`, `width`, `height`, `format`, `codec`, `version`, `background_index`,
+ * `background_color`, `pixel_aspect_ratio`, `first_frame_full`, `first_frame_standalone`,
+ * `first_frame_palette_source`, `first_frame_dispose`, `first_frame_transparent_index`,
+ * `any_later_transparent_index`, `safe_first_frame_preview`, `frameless`, `has_color_profile`, `loop_count`,
+ * `has_xmp`, `bytes_read`, `file_bytes_read`, `memory_peak`, `expansion_ratio`, `sha256`, `trailing_bytes`,
+ * `polyglot_suspect`, `palette_changes`, `distinct_palettes`, `used_colors`, `palette_utilization`,
+ * `max_simultaneous_colors`, `grayscale`, `max_lzw_code_width`, `warnings`, `first_frame_bit_depth`,
+ * `zero_delay_frames`, `all_zero_delays`, `longest_frame_index`, `longest_frame_delay`, `dominant_color`,
+ * `average_color`, `dominant_colors`, `blurhash`, `phash`, `alpha_explanation`, `rendered_alpha`,
+ * `rendered_alpha_frame`, `fps_timeline`, `frames_detail`, `truncated`, `frame_at`, `app_extensions`, `comments`,
+ * `xmp`, `frame_offsets`, `content_class`, `content_scores`, `ui_animation`, `ui_factors`, `should_be_static`,
+ * `effectively_static`, `duplicate_frames`, `canvas_valid`, `canvas_overflow_frames`, `has_keyframe`, `decode_ms`,
+ * `header_ms`, `first_frame_ms`, `remaining_frames_ms`, `bytes_per_sec`
+ *
+ * With `--files-from`, each record starts with `path`. `sha256` is `null` without the `hash` feature, and
+ * a requested field can still be `null` on its own, like `first_frame_bit_depth` for a first frame without a palette.
  */
 
 use std::{
+    fmt,
     fs::File,
-    io::BufReader,
+    io::{self, BufRead, BufReader, BufWriter, Write},
     num::NonZeroU64,
     path::{Path, PathBuf},
 };
 
-use gif::{ColorOutput, DecodeOptions, DisposalMethod, MemoryLimit};
+use argh::FromArgs;
+use gif_probe::{
+    csv_error_record, error_record, probe, rejected_record, serve_json_lines, serve_length_prefixed,
+    ClassifyRules, DurationUnit, GifProbe, ImageFormat, JsonOptions, ProbeError, ProbeOptions, UiRules,
+};
+
+#[cfg(feature = "http")]
+use gif_probe::serve_http;
+#[cfg(unix)]
+use gif_probe::serve_unix_socket;
+#[cfg(feature = "png")]
+use gif_probe::FramePosition;
+
+/// How the result is printed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    Oneline,
+    BinaryHeader,
+    Headers,
+    Csv,
+    MessagePack,
+    Cbor,
+}
+
+/// The formats [`Format::single_file`] is true for, for errors
+const SINGLE_FILE_FORMATS: &str = "`--format binary-header`, `headers`, `msgpack` and `cbor`";
+
+impl Format {
+    /// Whether the output only describes one file, with no path or separator to tell several files' records apart
+    fn single_file(self) -> bool {
+        matches!(
+            self,
+            Format::BinaryHeader | Format::Headers | Format::MessagePack | Format::Cbor
+        )
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "oneline" => Ok(Format::Oneline),
+            "binary-header" => Ok(Format::BinaryHeader),
+            "headers" => Ok(Format::Headers),
+            "csv" => Ok(Format::Csv),
+            "msgpack" => Ok(Format::MessagePack),
+            "cbor" => Ok(Format::Cbor),
+            _ => Err(format!(
+                concat!(
+                    "unknown format `{}`, expected `json`, `oneline`, `binary-header`, `headers`, `csv`, ",
+                    "`msgpack` or `cbor`"
+                ),
+                s
+            )),
+        }
+    }
+}
+
+/// Digest of the input for `--hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(format!("unknown hash `{s}`, expected `sha256`")),
+        }
+    }
+}
 
-pub struct GifProbe {
-    pub alpha: bool,
-    pub max_colors: u16,
-    pub duration: u64,
-    pub frames: u64,
+/// `WxH` canvas size for `--expect-dimensions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
     pub width: u16,
     pub height: u16,
 }
 
+impl std::str::FromStr for Dimensions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+
+        match parsed {
+            Some((width, height)) => Ok(Dimensions { width, height }),
+            None => Err(format!("invalid dimensions `{s}`, expected `WxH` like `480x270`")),
+        }
+    }
+}
+
+/// Exit code when any `--expect-*` assertion fails
+const EXPECTATION_FAILED: i32 = 3;
+
+/// Exit code when `--require-opaque-keyframe` isn't met
+const NO_OPAQUE_KEYFRAME: i32 = 4;
+
+/// Exit code when `validate` finds a problem with any file
+const INVALID_FILE: i32 = 5;
+
+/// Exit code when probing takes longer than `--timeout-ms`
+const TIMED_OUT: i32 = 6;
+
+/// Exit code when a single input is over a limit, like `-d` or `--max-file-size`
+const REJECTED: i32 = 7;
+
 /// Probes a GIF to detect if it actually has transparent pixels,
 /// and accumulates misc data while we're at it.
 #[derive(argh::FromArgs)]
+pub struct Cli {
+    #[argh(subcommand)]
+    pub command: Command,
+}
+
+// argh needs each variant to be the subcommand itself, so `probe`'s flags can't be boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Probe(Arguments),
+    Validate(ValidateArguments),
+    Extract(ExtractArguments),
+    Serve(ServeArguments),
+}
+
+/// First arguments that [`parse_command`] parses with [`Cli`], rather than as flags of `probe`
+const SUBCOMMANDS: [&str; 7] = ["probe", "validate", "extract", "serve", "help", "-h", "--help"];
+
+/// probe GIFs for transparency and other details, the default without a subcommand
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "probe")]
 pub struct Arguments {
     /// stop processing after this duration is reached
     #[argh(option, short = 'j')]
     pub max_duration: Option<u64>,
 
-    /// panic if the given number of pixels is more than this
+    /// stop processing after this many frames, reporting whether any were left as `truncated`
+    #[argh(option, short = 'f')]
+    pub max_frames: Option<NonZeroU64>,
+
+    /// exit with code 6 if probing takes longer than this many milliseconds of wall-clock time
+    #[argh(option, short = 't')]
+    pub timeout_ms: Option<u64>,
+
+    /// reject the input (exit 7) if it is over this many bytes, checked up front for files and while reading stdin
+    #[argh(option)]
+    pub max_file_size: Option<u64>,
+
+    /// reject the input (exit 7) if the given number of pixels is more than this
     #[argh(option, short = 'd')]
     pub max_pixels: Option<u64>,
 
+    /// reject the input (exit 7) if every frame's pixels add up to more than this many per byte read
+    #[argh(option)]
+    pub max_expansion: Option<f64>,
+
     /// don't decode if the decoder would allocate more than this (in bytes)
     #[argh(option, short = 'm')]
     pub max_memory: Option<NonZeroU64>,
 
-    /// path to the GIF file, or `-` to read from stdin
+    /// path to the GIF file, or `-` to read from stdin, repeat to probe several files
     #[argh(option, short = 'i')]
-    pub input: PathBuf,
+    pub input: Vec<PathBuf>,
+
+    /// probe the input as `gif`, `webp`, `png` or `avif` instead of going by its first bytes
+    #[argh(option)]
+    pub format_hint: Option<ImageFormat>,
+
+    /// write the results to this file instead of stdout, replacing it only once they're all written
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// read newline-separated paths from this file, or `-` for stdin, and print one result per line
+    #[argh(option)]
+    pub files_from: Option<PathBuf>,
+
+    /// same as `--files-from -`
+    #[argh(switch)]
+    pub paths_from_stdin: bool,
+
+    /// with a path list, probe this many files at once and print results as they finish (default 1)
+    #[argh(option, default = "1")]
+    pub jobs: usize,
+
+    /// with `-i` naming a directory, probe every matching file in it and its subdirectories
+    #[argh(switch)]
+    pub recursive: bool,
+
+    /// with `--recursive`, only probe files whose names match this pattern of `*` and `?` wildcards
+    #[argh(option)]
+    pub glob: Option<String>,
+
+    /// write the first frame to this path as a PNG (requires the `png` feature)
+    #[argh(option)]
+    pub thumbnail: Option<PathBuf>,
+
+    /// report the instantaneous FPS implied by each frame's delay
+    #[argh(switch)]
+    pub fps_timeline: bool,
+
+    /// reject files that don't strictly follow the GIF block structure
+    #[argh(switch)]
+    pub strict: bool,
+
+    /// output format: `json` (default), `oneline`, `binary-header`, `headers`, `csv`, `msgpack` or `cbor`
+    #[argh(option, default = "Format::Json")]
+    pub format: Format,
+
+    /// round floating point fields to this many decimal places (default 3)
+    #[argh(option, default = "3")]
+    pub float_precision: u32,
+
+    /// write every optional JSON field, as `null` when it wasn't requested, so all records have the same keys
+    #[argh(switch)]
+    pub normalize_output: bool,
+
+    /// indent JSON output over several lines for reading
+    #[argh(switch)]
+    pub pretty: bool,
+
+    /// unit of `duration` and the other summary delays: `cs` (default, as GIF stores them), `ms` or `s`
+    #[argh(option, default = "DurationUnit::Centiseconds")]
+    pub duration_unit: DurationUnit,
+
+    /// check that frames stay within the logical screen and that one of them covers all of it
+    #[argh(switch)]
+    pub validate_canvas: bool,
+
+    /// report the byte offset of each frame in the input
+    #[argh(switch)]
+    pub frame_offsets: bool,
+
+    /// report header fields of the first K frames, while still counting all of them
+    #[argh(option)]
+    pub detail_frames: Option<u64>,
+
+    /// print a line of JSON with the header fields of each frame, followed by the usual output
+    #[argh(switch)]
+    pub per_frame: bool,
+
+    /// report statistics such as the first frame's palette bit depth and the number of zero-delay frames
+    #[argh(switch)]
+    pub frame_stats: bool,
+
+    /// estimate the first frame's most frequent color from a sample of its pixels
+    #[argh(switch)]
+    pub dominant_color: bool,
+
+    /// report the mean color of the first frame's opaque pixels
+    #[argh(switch)]
+    pub average_color: bool,
+
+    /// report the first frame's mean color and up to this many of its most frequent colors, for placeholders
+    #[argh(option)]
+    pub colors: Option<usize>,
+
+    /// compute a BlurHash of the first frame, for blurred placeholders
+    #[argh(switch)]
+    pub blurhash: bool,
+
+    /// compute a 64-bit perceptual hash of the first frame, for finding re-uploads of the same GIF
+    #[argh(switch)]
+    pub phash: bool,
+
+    /// count how often frames switch palettes, and how many distinct palettes they use
+    #[argh(switch)]
+    pub palette_stats: bool,
+
+    /// count the distinct colors used across all frames (decodes every frame)
+    #[argh(switch)]
+    pub count_used_colors: bool,
+
+    /// report the most distinct colors visible at once (decodes and composites every frame)
+    #[argh(switch)]
+    pub max_simultaneous_colors: bool,
+
+    /// report whether every color drawn is a shade of gray (decodes every frame)
+    #[argh(switch)]
+    pub grayscale: bool,
+
+    /// report the widest LZW code read in any frame (up to 12 bits), showing how far the dictionary grew
+    #[argh(switch)]
+    pub lzw_stats: bool,
+
+    /// count the bytes after the trailer and flag archives or scripts hidden there (reads the input to EOF)
+    #[argh(switch)]
+    pub trailing_data: bool,
+
+    /// explain which condition made `alpha` true, or that none did
+    #[argh(switch)]
+    pub explain_alpha: bool,
+
+    /// composite every frame to tell exactly whether any renders with transparent pixels (decodes every frame)
+    #[argh(switch)]
+    pub exhaustive: bool,
+
+    /// when reading from stdin, read it to EOF after probing so `bytes_read` is the full input size
+    #[argh(switch)]
+    pub drain_stdin: bool,
+
+    /// classify the content as a screen recording, animation or static image
+    #[argh(switch)]
+    pub classify: bool,
+
+    /// memory-map input files instead of reading them (requires the `mmap` feature)
+    #[argh(switch)]
+    pub mmap: bool,
+
+    /// probe GIFs as they're created or modified in this directory (requires the `watch` feature)
+    #[argh(option)]
+    pub watch: Option<PathBuf>,
+
+    /// answer JSON requests read from stdin, one per line, with one JSON response line each
+    #[argh(switch)]
+    pub stdio_server: bool,
+
+    /// include a digest of the whole input, only `sha256` (requires the `hash` feature)
+    #[argh(option)]
+    pub hash: Option<HashAlgorithm>,
+
+    /// with a path list, print one object keyed by each file's hash (requires `--hash`)
+    #[argh(switch)]
+    pub key_by_hash: bool,
+
+    /// exit with code 3 if the number of frames isn't this
+    #[argh(option)]
+    pub expect_frames: Option<u64>,
+
+    /// exit with code 3 if `alpha` isn't this, `true` or `false`
+    #[argh(option)]
+    pub expect_alpha: Option<bool>,
+
+    /// exit with code 3 if the canvas size isn't this, as `WxH`
+    #[argh(option)]
+    pub expect_dimensions: Option<Dimensions>,
+
+    /// flag small, low-color, infinitely looping UI animations like spinners
+    #[argh(switch)]
+    pub detect_ui: bool,
+
+    /// largest width or height that `--detect-ui` considers small (default 128)
+    #[argh(option, default = "UiRules::default().max_size")]
+    pub ui_max_size: u16,
+
+    /// most colors that `--detect-ui` considers few (default 32)
+    #[argh(option, default = "UiRules::default().max_colors")]
+    pub ui_max_colors: u16,
+
+    /// most frames that `--detect-ui` considers few (default 60)
+    #[argh(option, default = "UiRules::default().max_frames")]
+    pub ui_max_frames: u64,
+
+    /// report the header and transparency of the frame at this index, and stop after it
+    #[argh(option)]
+    pub frame_at: Option<u64>,
+
+    /// report every application extension with its payload as base64
+    #[argh(switch)]
+    pub dump_app_extensions: bool,
+
+    /// report the text of every comment extension
+    #[argh(switch)]
+    pub comments: bool,
+
+    /// report the XMP metadata packet
+    #[argh(switch)]
+    pub xmp: bool,
+
+    /// exit with code 4 unless the first frame is opaque and covers the whole canvas
+    #[argh(switch)]
+    pub require_opaque_keyframe: bool,
+
+    /// suggest whether the animation could be a static image (decodes every frame)
+    #[argh(switch)]
+    pub suggest_static: bool,
+
+    /// report how long probing took, in total and for the header, the first frame and the rest of the frames
+    #[argh(switch)]
+    pub timing: bool,
 }
 
-trait ResultExt<T> {
-    fn expect_path(self, path: &Path, msg: &str) -> T;
+/// check GIFs for structural problems, printing an error record for each file that has one
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "validate")]
+pub struct ValidateArguments {
+    /// path to a GIF file, repeat to validate several files
+    #[argh(option, short = 'i')]
+    pub input: Vec<PathBuf>,
+
+    /// reject files with more pixels than this
+    #[argh(option, short = 'd')]
+    pub max_pixels: Option<u64>,
+
+    /// don't decode if the decoder would allocate more than this (in bytes)
+    #[argh(option, short = 'm')]
+    pub max_memory: Option<NonZeroU64>,
 }
 
-impl<T, E> ResultExt<T> for Result<T, E>
-where
-    E: std::fmt::Debug,
-{
-    #[inline(always)]
-    #[track_caller]
-    fn expect_path(self, path: &Path, msg: &str) -> T {
-        self.unwrap_or_else(|e| panic!("Error {msg} for file: {}: {e:?}", path.display()))
-    }
+/// write a frame of a GIF as an RGBA PNG, the first by default (requires the `png` feature)
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "extract")]
+pub struct ExtractArguments {
+    /// path to the GIF file, or `-` to read from stdin
+    #[argh(option, short = 'i')]
+    pub input: PathBuf,
+
+    /// path to write the PNG to
+    #[argh(option, short = 'o')]
+    pub output: PathBuf,
+
+    /// 0-based index of the frame to write, composited over the frames before it
+    #[argh(option)]
+    pub frame: Option<u64>,
+
+    /// write the frame showing this many milliseconds into the animation instead
+    #[argh(option)]
+    pub at_ms: Option<u64>,
+
+    /// reject files with more pixels than this
+    #[argh(option, short = 'd')]
+    pub max_pixels: Option<u64>,
+
+    /// don't decode if the decoder would allocate more than this (in bytes)
+    #[argh(option, short = 'm')]
+    pub max_memory: Option<NonZeroU64>,
 }
 
-fn main() {
-    let args: Arguments = argh::from_env();
+/// answer JSON request lines or length-prefixed payloads read from stdin, with one JSON response line each
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "serve")]
+pub struct ServeArguments {
+    /// read payloads to probe from stdin, each after its length as a 4-byte big-endian integer,
+    /// instead of JSON request lines
+    #[argh(switch)]
+    pub stdio: bool,
 
-    let path = args.input.as_path();
+    /// listen on a Unix domain socket at this path instead, answering each connection's bytes concurrently
+    #[argh(option)]
+    pub socket: Option<PathBuf>,
 
-    // try to unbuffer stdin on windows and unix, otherwise use a boxed trait object
-    let reader = BufReader::new(match path {
-        #[cfg(windows)]
-        path if path.as_os_str() == "-" => unsafe {
-            use std::os::windows::io::{AsRawHandle, FromRawHandle};
-            File::from_raw_handle(std::io::stdin().as_raw_handle())
-        },
+    /// listen for HTTP on this address instead, like `127.0.0.1:8080`, answering `POST /probe`
+    #[argh(option)]
+    pub http: Option<String>,
 
-        #[cfg(unix)]
-        path if path.as_os_str() == "-" => unsafe {
-            use std::os::fd::{AsRawFd, FromRawFd};
-            File::from_raw_fd(std::io::stdin().as_raw_fd())
-        },
+    /// largest request body `--http` accepts, in bytes (default 64 MiB)
+    #[argh(option, default = "64 << 20")]
+    pub max_body_size: u64,
 
-        #[cfg(any(windows, unix))] // unboxed file, no need for dyn trait
-        path => File::open(path).expect_path(path, "opening file"),
+    /// stop processing each file after this duration is reached
+    #[argh(option, short = 'j')]
+    pub max_duration: Option<u64>,
 
-        #[cfg(not(any(windows, unix)))] // can't unbuffer, will be double-buffered, oh well
-        path if path.as_os_str() == "-" => Box::new(std::io::stdin().lock()) as Box<dyn std::io::Read>,
+    /// stop processing each file after this many frames
+    #[argh(option, short = 'f')]
+    pub max_frames: Option<NonZeroU64>,
 
-        #[cfg(not(any(windows, unix)))]
-        path => Box::new(File::open(path).expect_path(path, "opening file")) as Box<dyn std::io::Read>,
-    });
+    /// reject files larger than this many bytes
+    #[argh(option)]
+    pub max_file_size: Option<u64>,
 
-    let mut opts = DecodeOptions::new();
-
-    opts.set_color_output(ColorOutput::Indexed);
-    opts.check_frame_consistency(true);
-    opts.allow_unknown_blocks(false);
-    opts.check_lzw_end_code(false);
-    opts.set_memory_limit(MemoryLimit::Bytes(
-        // user-specified or 20 MiB
-        args.max_memory
-            // SAFETY: Obviously non-zero
-            .unwrap_or(unsafe { NonZeroU64::new_unchecked(1024 * 1024 * 20) }),
-    ));
-
-    // if in the future `gif` requires `Seek` it'll silently become incompatible with stdin,
-    // so assert that `read_info` can work with only `Read`
-    #[inline(always)]
-    fn assert_read_only<R: std::io::Read>(path: &Path, reader: R, opts: DecodeOptions) -> gif::Decoder<R> {
-        opts.read_info(reader).expect_path(path, "reading the GIF")
-    }
+    /// reject files with more pixels than this
+    #[argh(option, short = 'd')]
+    pub max_pixels: Option<u64>,
 
-    let mut decoder = assert_read_only(path, reader, opts);
+    /// don't decode if the decoder would allocate more than this (in bytes)
+    #[argh(option, short = 'm')]
+    pub max_memory: Option<NonZeroU64>,
 
-    let mut probe = GifProbe {
-        width: decoder.width(),
-        height: decoder.height(),
-        alpha: false,
-        max_colors: 0,
-        duration: 0,
-        frames: 0,
-    };
+    /// round floating point fields to this many decimal places (default 3)
+    #[argh(option, default = "3")]
+    pub float_precision: u32,
 
-    if matches!(args.max_pixels, Some(m) if m < (probe.width as u64 * probe.height as u64)) {
-        panic!("Image too large!");
+    /// write every optional field, as `null` when it wasn't collected
+    #[argh(switch)]
+    pub normalize_output: bool,
+
+    /// unit of `duration` and the other summary delays: `cs` (default), `ms` or `s`
+    #[argh(option, default = "DurationUnit::Centiseconds")]
+    pub duration_unit: DurationUnit,
+}
+
+impl Arguments {
+    /// Where to read paths from, when probing a list of files
+    fn path_list(&self) -> Option<&Path> {
+        self.files_from.as_deref().or(self.paths_from_stdin.then(|| Path::new("-")))
     }
 
-    if let Some(p) = decoder.global_palette() {
-        probe.max_colors = u16::try_from(p.len() / 3).expect_path(path, "converting color count");
+    fn json_options(&self) -> JsonOptions {
+        JsonOptions {
+            float_precision: self.float_precision,
+            normalize: self.normalize_output,
+            pretty: self.pretty,
+            duration_unit: self.duration_unit,
+        }
     }
 
-    if let Some(frame) = decoder.read_next_frame().expect_path(path, "reading the first frame") {
-        probe.alpha |= matches!(frame.transparent, Some(tr) if frame.buffer.contains(&tr));
-        probe.frames += 1;
-        probe.duration += frame.delay as u64;
+    fn probe_options(&self) -> ProbeOptions {
+        let options = ProbeOptions::builder()
+            .max_duration(self.max_duration)
+            .max_frames(self.max_frames)
+            .max_file_size(self.max_file_size)
+            .max_pixels(self.max_pixels)
+            .max_expansion(self.max_expansion)
+            .max_memory(self.max_memory)
+            .format_hint(self.format_hint)
+            .fps_timeline(self.fps_timeline)
+            .strict(self.strict)
+            .validate_canvas(self.validate_canvas)
+            .frame_offsets(self.frame_offsets)
+            .detail_frames(if self.per_frame { Some(u64::MAX) } else { self.detail_frames })
+            .frame_stats(self.frame_stats)
+            .dominant_color(self.dominant_color)
+            .average_color(self.average_color || self.colors.is_some())
+            .dominant_colors(self.colors)
+            .blurhash(self.blurhash)
+            .phash(self.phash)
+            .frame_at(self.frame_at)
+            .app_extensions(self.dump_app_extensions)
+            .comments(self.comments)
+            .xmp(self.xmp)
+            .palette_stats(self.palette_stats)
+            .count_used_colors(self.count_used_colors)
+            .max_simultaneous_colors(self.max_simultaneous_colors)
+            .grayscale(self.grayscale)
+            .lzw_stats(self.lzw_stats)
+            .trailing_data(self.trailing_data)
+            .explain_alpha(self.explain_alpha)
+            .exhaustive_alpha(self.exhaustive)
+            .drain(self.drain_stdin && matches!(self.input[..], [ref input] if input.as_os_str() == "-"))
+            .classify(self.classify.then(ClassifyRules::default))
+            .detect_ui(self.detect_ui.then_some(UiRules {
+                max_size: self.ui_max_size,
+                max_colors: self.ui_max_colors,
+                max_frames: self.ui_max_frames,
+            }))
+            .suggest_static(self.suggest_static)
+            .timing(self.timing);
 
-        if let Some(ref p) = frame.palette {
-            probe.max_colors =
-                probe.max_colors.max(u16::try_from(p.len() / 3).expect_path(path, "converting color count"));
-        }
+        #[cfg(feature = "png")]
+        let options = options.thumbnail(self.thumbnail.clone());
+
+        #[cfg(feature = "hash")]
+        let options = options.hash(self.hash == Some(HashAlgorithm::Sha256));
+
+        options.build()
     }
+}
 
-    let max_duration = args.max_duration.unwrap_or(u64::MAX);
+impl Arguments {
+    /// Prints each `--expect-*` assertion and requirement that `probe` fails to stderr,
+    /// returning the exit code for them, if any.
+    fn check(&self, path: &Path, probe: &GifProbe) -> Option<i32> {
+        let mut code = None;
 
-    while let Some(frame) = decoder.next_frame_info().expect_path(path, "reading a frame") {
-        probe.alpha |= frame.dispose == DisposalMethod::Background && frame.width > 0 && frame.height > 0;
-        probe.frames += 1;
-        probe.duration += frame.delay as u64;
+        let mut fail = |msg: String| {
+            eprintln!("Expectation failed for file: {}: {msg}", path.display());
+            code = Some(EXPECTATION_FAILED);
+        };
+
+        if let Some(frames) = self.expect_frames.filter(|&f| f != probe.frames) {
+            fail(format!("expected {frames} frames, found {}", probe.frames));
+        }
+
+        if let Some(alpha) = self.expect_alpha.filter(|&a| a != probe.alpha) {
+            fail(format!("expected alpha to be {alpha}, found {}", probe.alpha));
+        }
 
-        if let Some(ref p) = frame.palette {
-            probe.max_colors =
-                probe.max_colors.max(u16::try_from(p.len() / 3).expect_path(path, "converting color count"));
+        if let Some(dim) = self.expect_dimensions.filter(|d| (d.width, d.height) != (probe.width, probe.height)) {
+            let (width, height) = (probe.width, probe.height);
+            fail(format!("expected {}x{}, found {width}x{height}", dim.width, dim.height));
         }
 
-        if probe.duration >= max_duration {
-            break;
+        if self.require_opaque_keyframe && !probe.first_frame_standalone {
+            eprintln!(
+                "Requirement failed for file: {}: the first frame isn't opaque and covering the whole canvas",
+                path.display()
+            );
+
+            code = code.or(Some(NO_OPAQUE_KEYFRAME));
         }
+
+        code
     }
+}
+
+trait ResultExt<T> {
+    fn expect_path(self, path: &Path, msg: &str) -> T;
+}
 
-    println!(
-        r#"{{"alpha":{},"max_colors":{},"duration":{},"frames":{},"width":{},"height":{}}}"#,
-        probe.alpha, probe.max_colors, probe.duration, probe.frames, probe.width, probe.height
-    );
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: std::fmt::Debug,
+{
+    #[inline(always)]
+    #[track_caller]
+    fn expect_path(self, path: &Path, msg: &str) -> T {
+        self.unwrap_or_else(|e| panic!("Error {msg} for file: {}: {e:?}", path.display()))
+    }
+}
+
+/// Feature-gated flags are always recognized by the parser, so a minimal build
+/// can say what's missing instead of rejecting the flag as unknown or ignoring it.
+macro_rules! require_feature {
+    ($feature:literal, $flag:literal, $given:expr) => {
+        #[cfg(not(feature = $feature))]
+        if $given {
+            eprintln!(
+                "Error: `{}` requires gif_probe to be built with the `{}` feature",
+                $flag, $feature
+            );
+            std::process::exit(1);
+        }
+    };
+}
+
+/// Newline-separated paths read from `list`, or stdin for `-`, skipping blank lines
+fn list_paths(list: &Path) -> impl Iterator<Item = PathBuf> + Send {
+    let reader: Box<dyn std::io::Read + Send> = match list.as_os_str() == "-" {
+        true => Box::new(std::io::stdin()),
+        false => Box::new(File::open(list).expect_path(list, "opening the path list")),
+    };
+
+    BufReader::new(reader).lines().filter_map(|line| {
+        let mut line = line.expect("reading the path list");
+
+        if line.ends_with('\r') {
+            line.pop();
+        }
+
+        (!line.is_empty()).then(|| PathBuf::from(line))
+    })
+}
+
+/// Why a file of many wasn't probed, for its error record
+struct Failure {
+    error: String,
+    /// From [`ProbeError::rejection`], when the file is over a limit rather than invalid
+    rejected: Option<&'static str>,
+}
+
+impl Failure {
+    /// The JSON error record, with `rejected` when the file is over a limit
+    fn record(&self, path: &Path) -> String {
+        match self.rejected {
+            Some(rejected) => rejected_record(Some(path), rejected, &self.error),
+            None => error_record(path, &self.error),
+        }
+    }
+}
+
+impl From<ProbeError> for Failure {
+    fn from(err: ProbeError) -> Self {
+        Failure {
+            error: err.to_string(),
+            rejected: err.rejection(),
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Self {
+        Failure {
+            error: err.to_string(),
+            rejected: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+/// Opens and probes a single file of many, with errors kept for the output rather than panicking.
+#[cfg_attr(not(feature = "mmap"), allow(unused_variables))]
+fn probe_path(path: &Path, args: &Arguments, opts: &ProbeOptions) -> Result<GifProbe, Failure> {
+    #[cfg(feature = "mmap")]
+    if args.mmap {
+        let map = map_file(path)?;
+        opts.check_file_size(map.len() as u64)?;
+        return Ok(probe(&map[..], opts)?);
+    }
+
+    let file = File::open(path)?;
+    opts.check_file_size(file.metadata()?.len())?;
+    Ok(probe(BufReader::new(file), opts)?)
+}
+
+/// Files under `root` whose names match `glob`, or that have a `.gif` extension without one, sorted by path.
+///
+/// Directories that can't be read are reported on stderr and skipped. Symlinked directories aren't followed.
+fn walk_dir(root: &Path, glob: Option<&str>) -> Vec<PathBuf> {
+    let matches = |path: &Path| match glob {
+        Some(glob) => path.file_name().is_some_and(|name| glob_match(glob, &name.to_string_lossy())),
+        None => path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif")),
+    };
+
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("{}", error_record(&dir, &e));
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let entry = entry.expect_path(&dir, "listing directory");
+
+            match entry.file_type().expect_path(&entry.path(), "reading file type") {
+                ty if ty.is_dir() => dirs.push(entry.path()),
+                _ if matches(&entry.path()) => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+
+    files.sort_unstable();
+    files
+}
+
+/// Whether `name` matches `glob` as a whole, where `*` matches any run of characters and `?` any one character.
+fn glob_match(glob: &str, name: &str) -> bool {
+    let (glob, name): (Vec<char>, Vec<char>) = (glob.chars().collect(), name.chars().collect());
+    let (mut g, mut n) = (0, 0);
+
+    // where to resume after the last `*` if the rest fails to match, letting it swallow one more character
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g, n));
+                g += 1;
+                continue;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((star, at)) => {
+                backtrack = Some((star, at + 1));
+                (g, n) = (star + 1, at + 1);
+            }
+            None => return false,
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// Probes each of `paths`, printing a result or error record per line as each file finishes.
+/// Paths are shown relative to `root`, if given.
+///
+/// Returns the exit code for the first file that failed a check, if any.
+fn probe_paths(
+    args: &Arguments,
+    out: &mut Output,
+    paths: impl Iterator<Item = PathBuf> + Send,
+    root: Option<&Path>,
+) -> Option<i32> {
+    use std::sync::{mpsc, Mutex};
+
+    let opts = args.probe_options();
+    let paths = Mutex::new(paths);
+    let mut code = None;
+
+    print_header(args, out);
+
+    std::thread::scope(|s| {
+        let (tx, rx) = mpsc::sync_channel(args.jobs.max(1));
+
+        for _ in 0..args.jobs.max(1) {
+            let (tx, paths, opts) = (tx.clone(), &paths, &opts);
+
+            s.spawn(move || loop {
+                let Some(path) = paths.lock().unwrap().next() else {
+                    break;
+                };
+
+                let result = probe_path(&path, args, opts);
+
+                if tx.send((path, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // the workers hold the only senders left, so this ends once they're all done
+        drop(tx);
+
+        for (path, result) in rx {
+            let path = root.and_then(|root| path.strip_prefix(root).ok()).unwrap_or(&path);
+
+            if let Ok(ref probe) = result {
+                code = code.or(args.check(path, probe));
+            }
+
+            print_record(args, out, path, result);
+        }
+    });
+
+    code
+}
+
+/// Probes each of several `-i` paths, printing all results as one JSON array, or one line each otherwise.
+///
+/// Returns the exit code for the first file that failed a check, if any.
+fn probe_inputs(args: &Arguments, out: &mut Output) -> Option<i32> {
+    let opts = args.probe_options();
+    let mut code = None;
+
+    let json = args.format == Format::Json;
+
+    if json {
+        out.line("[");
+    }
+
+    print_header(args, out);
+
+    for (i, path) in args.input.iter().enumerate() {
+        let result = probe_path(path, args, &opts);
+
+        if let Ok(ref probe) = result {
+            code = code.or(args.check(path, probe));
+        }
+
+        if !json {
+            print_record(args, out, path, result);
+            continue;
+        }
+
+        let record = match result {
+            Ok(probe) => probe.to_json_record(path, &args.json_options()),
+            Err(e) => e.record(path),
+        };
+
+        let comma = if i + 1 < args.input.len() { "," } else { "" };
+        out.line(format_args!("{record}{comma}"));
+    }
+
+    if json {
+        out.line("]");
+    }
+
+    code
+}
+
+/// Where results are written, see [`Output::new`]
+enum Output {
+    Stdout(io::StdoutLock<'static>),
+
+    /// `--output`, written to `temp` then renamed over `path` by [`Output::finish`]
+    File {
+        file: BufWriter<File>,
+        temp: PathBuf,
+        path: PathBuf,
+    },
+}
+
+impl Output {
+    /// Stdout, or a temporary file next to `--output`, so the rename to it is atomic
+    fn new(args: &Arguments) -> Output {
+        let Some(ref path) = args.output else {
+            return Output::Stdout(io::stdout().lock());
+        };
+
+        let Some(name) = path.file_name() else {
+            eprintln!("Error: `--output` must name a file, not {}", path.display());
+            std::process::exit(1);
+        };
+
+        let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .expect_path(&temp, "creating the temporary output");
+
+        Output::File {
+            file: BufWriter::new(file),
+            temp,
+            path: path.clone(),
+        }
+    }
+
+    fn line(&mut self, line: impl fmt::Display) {
+        writeln!(self, "{line}").expect("writing the output");
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        self.write_all(bytes).expect("writing the output");
+    }
+
+    /// Flushes everything written, moving the temporary file into place for `--output`.
+    fn finish(self) {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush().expect("writing the output"),
+            Output::File { file, temp, path } => {
+                let file = file.into_inner().map_err(|e| e.into_error()).expect_path(&temp, "writing the output");
+
+                // make sure the contents are on disk before they can replace the old file
+                file.sync_all().expect_path(&temp, "writing the output");
+                std::fs::rename(&temp, &path).expect_path(&path, "replacing the output");
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File { file, .. } => file.flush(),
+        }
+    }
+}
+
+/// Prints the header row of formats that have one, before the first [`print_record`].
+fn print_header(args: &Arguments, out: &mut Output) {
+    if args.format == Format::Csv {
+        out.line(GifProbe::CSV_HEADER);
+    }
+}
+
+/// Prints the result for one file of many as a single line.
+fn print_record(args: &Arguments, out: &mut Output, path: &Path, result: Result<GifProbe, Failure>) {
+    match (args.format, result) {
+        (Format::Oneline, Ok(probe)) => out.line(format_args!("{}: {}", path.display(), probe.to_oneline())),
+        (Format::Oneline, Err(e)) => out.line(format_args!("{}: error: {e}", path.display())),
+        (Format::Csv, Ok(probe)) => out.line(probe.to_csv_record(path, &args.json_options())),
+        (Format::Csv, Err(e)) => out.line(csv_error_record(path, &e)),
+        (_, Ok(probe)) => out.line(probe.to_json_record(path, &args.json_options())),
+        (_, Err(e)) => out.line(e.record(path)),
+    }
+}
+
+/// Probes GIFs created or modified in `dir`, printing a record per file once its events settle. Never returns.
+#[cfg(feature = "watch")]
+fn watch(args: &Arguments, dir: &Path) -> ! {
+    use std::{
+        collections::HashMap,
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    // a file being written produces a burst of events, wait for it to go quiet
+    const DEBOUNCE: Duration = Duration::from_millis(250);
+
+    let opts = args.probe_options();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).expect_path(dir, "creating the watcher");
+    watcher.watch(dir, RecursiveMode::NonRecursive).expect_path(dir, "watching directory");
+
+    let is_gif = |path: &Path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut out = Output::new(args);
+
+    print_header(args, &mut out);
+
+    loop {
+        match rx.recv_timeout(DEBOUNCE / 5) {
+            Ok(Ok(event)) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths.into_iter().filter(|path| is_gif(path)) {
+                    pending.insert(path, Instant::now());
+                }
+            }
+            Ok(Ok(_)) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => eprintln!("Error watching directory: {}: {e}", dir.display()),
+            Err(mpsc::RecvTimeoutError::Disconnected) => panic!("watcher stopped for: {}", dir.display()),
+        }
+
+        pending.retain(|path, last| {
+            if last.elapsed() < DEBOUNCE {
+                return true;
+            }
+
+            print_record(args, &mut out, path, probe_path(path, args, &opts));
+            false
+        });
+    }
+}
+
+/// Probes each path in `list`, printing a single object mapping each file's SHA-256 to its result.
+///
+/// The first file with given contents wins, and errors go to stderr since files that failed have no key.
+/// Returns the exit code for the first file that failed a check, if any.
+#[cfg(feature = "hash")]
+fn probe_paths_by_hash(args: &Arguments, out: &mut Output, list: &Path) -> Option<i32> {
+    let opts = args.probe_options();
+    let mut seen = std::collections::HashSet::new();
+    let mut code = None;
+
+    out.raw(b"{");
+
+    for path in list_paths(list) {
+        match probe_path(&path, args, &opts) {
+            Ok(probe) => {
+                code = code.or(args.check(&path, &probe));
+
+                let key = probe.sha256_hex().expect("hashing is enabled");
+                let sep = if seen.is_empty() { "" } else { "," };
+
+                if seen.insert(key.clone()) {
+                    let json = probe.to_json_record(&path, &args.json_options());
+                    out.raw(format!("{sep}\"{key}\":{json}").as_bytes());
+                }
+            }
+            Err(e) => eprintln!("{}", e.record(&path)),
+        }
+    }
+
+    out.line("}");
+
+    code
+}
+
+/// Opens the input file, or stdin for `-`
+fn open(path: &Path) -> BufReader<impl std::io::Read> {
+    // try to unbuffer stdin on windows and unix, otherwise use a boxed trait object
+    BufReader::new(match path {
+        #[cfg(windows)]
+        path if path.as_os_str() == "-" => unsafe {
+            use std::os::windows::io::{AsRawHandle, FromRawHandle};
+            File::from_raw_handle(std::io::stdin().as_raw_handle())
+        },
+
+        #[cfg(unix)]
+        path if path.as_os_str() == "-" => unsafe {
+            use std::os::fd::{AsRawFd, FromRawFd};
+            File::from_raw_fd(std::io::stdin().as_raw_fd())
+        },
+
+        #[cfg(any(windows, unix))] // unboxed file, no need for dyn trait
+        path => File::open(path).expect_path(path, "opening file"),
+
+        #[cfg(not(any(windows, unix)))] // can't unbuffer, will be double-buffered, oh well
+        path if path.as_os_str() == "-" => Box::new(std::io::stdin().lock()) as Box<dyn std::io::Read>,
+
+        #[cfg(not(any(windows, unix)))]
+        path => Box::new(File::open(path).expect_path(path, "opening file")) as Box<dyn std::io::Read>,
+    })
+}
+
+/// Exits the process with [`TIMED_OUT`] unless [`finish`](Self::finish)ed in time, for `--timeout-ms`.
+///
+/// The decoder can't be interrupted, so a crafted file that's slow to decode is only stopped by exiting.
+struct Watchdog(std::sync::Arc<std::sync::Mutex<bool>>);
+
+impl Watchdog {
+    fn start(path: &Path, millis: u64) -> Self {
+        let finished = std::sync::Arc::new(std::sync::Mutex::new(false));
+        let (path, flag) = (path.to_owned(), finished.clone());
+
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(millis));
+
+            // held until exiting, so the result can't start being printed in the meantime
+            let finished = flag.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+            if !*finished {
+                eprintln!(
+                    "Error probing the GIF for file: {}: timed out after {millis}ms",
+                    path.display()
+                );
+                std::process::exit(TIMED_OUT);
+            }
+        });
+
+        Watchdog(finished)
+    }
+
+    /// Disarms the watchdog, once probing is done
+    fn finish(self) {
+        *self.0.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = true;
+    }
+}
+
+/// Reports a single input over a limit as its result rather than panicking, since it isn't invalid,
+/// then exits with [`REJECTED`].
+fn reject(args: &Arguments, path: &Path, rejected: &str, error: &ProbeError) -> ! {
+    // the other formats have nowhere to say why there's no result
+    match args.format {
+        Format::Json => {
+            let mut out = Output::new(args);
+            out.line(rejected_record(None, rejected, error));
+            out.finish();
+        }
+        _ => eprintln!("Rejected file: {}: {error}", path.display()),
+    }
+
+    std::process::exit(REJECTED);
+}
+
+/// Memory-maps the input file for `--mmap`
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> std::io::Result<memmap2::Mmap> {
+    let file = File::open(path)?;
+
+    // SAFETY: The mapping is only valid while nothing else truncates or modifies the file,
+    // which is documented as the caller's responsibility when passing `--mmap`.
+    unsafe { memmap2::Mmap::map(&file) }
+}
+
+fn main() {
+    match parse_command() {
+        Command::Probe(args) => probe_files(args),
+        Command::Validate(args) => validate(&args),
+        Command::Extract(args) => extract(&args),
+        Command::Serve(args) => {
+            let opts = ProbeOptions::builder()
+                .max_duration(args.max_duration)
+                .max_frames(args.max_frames)
+                .max_file_size(args.max_file_size)
+                .max_pixels(args.max_pixels)
+                .max_memory(args.max_memory)
+                .build();
+
+            let json = JsonOptions {
+                float_precision: args.float_precision,
+                normalize: args.normalize_output,
+                pretty: false,
+                duration_unit: args.duration_unit,
+            };
+
+            if args.stdio as u8 + args.socket.is_some() as u8 + args.http.is_some() as u8 > 1 {
+                eprintln!(
+                    "Error: `--stdio`, `--socket` and `--http` are different ways to serve, pass only one of them"
+                );
+                std::process::exit(1);
+            }
+
+            require_feature!("http", "--http", args.http.is_some());
+
+            match (&args.socket, &args.http) {
+                (Some(path), _) => serve_socket(path, &opts, &json),
+                #[cfg(feature = "http")]
+                (_, Some(addr)) => {
+                    if let Err(e) = serve_http(addr, &opts, &json, args.max_body_size) {
+                        eprintln!("Error serving HTTP on {addr}: {e}");
+                        std::process::exit(1);
+                    }
+                }
+                _ => serve_stdio(&opts, &json, args.stdio),
+            }
+        }
+    }
+}
+
+/// Parses the command line like [`argh::from_env`], except that flags without a subcommand are `probe`'s,
+/// as they were before there were subcommands.
+fn parse_command() -> Command {
+    let strings: Vec<String> = std::env::args().collect();
+    let strs: Vec<&str> = strings.iter().map(String::as_str).collect();
+
+    // a parent process can exec without even the program name
+    let Some(arg0) = strs.first() else {
+        eprintln!("Error: no program name in the arguments\nRun gif_probe --help for more information.");
+        std::process::exit(1);
+    };
+
+    let name = Path::new(arg0).file_name().and_then(|name| name.to_str()).unwrap_or(arg0);
+
+    let parsed = match strs.get(1) {
+        Some(arg) if SUBCOMMANDS.contains(arg) => Cli::from_args(&[name], &strs[1..]).map(|cli| cli.command),
+        _ => Arguments::from_args(&[name], &strs[1..]).map(Command::Probe),
+    };
+
+    parsed.unwrap_or_else(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            std::process::exit(0);
+        }
+        Err(()) => {
+            eprintln!("{}\nRun {name} --help for more information.", early_exit.output);
+            std::process::exit(1);
+        }
+    })
+}
+
+/// Answers requests on stdin for `serve` and `--stdio-server`, until it closes.
+fn serve_stdio(opts: &ProbeOptions, json: &JsonOptions, length_prefixed: bool) {
+    let stdin = std::io::stdin().lock();
+    let stdout = std::io::stdout().lock();
+
+    let served = match length_prefixed {
+        true => serve_length_prefixed(stdin, stdout, opts, json),
+        false => serve_json_lines(stdin, stdout, opts, json),
+    };
+
+    if let Err(e) = served {
+        eprintln!("Error serving requests: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Answers connections on a Unix domain socket for `serve --socket`, until accepting one fails.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn serve_socket(path: &Path, opts: &ProbeOptions, json: &JsonOptions) {
+    #[cfg(unix)]
+    if let Err(e) = serve_unix_socket(path, opts, json) {
+        eprintln!("Error serving {}: {e}", path.display());
+        std::process::exit(1);
+    }
+
+    #[cfg(not(unix))]
+    {
+        eprintln!("Error: `--socket` is only supported on Unix");
+        std::process::exit(1);
+    }
+}
+
+/// Probes each file in strict mode and with its canvas validated, printing an error record for each one
+/// with a problem. Exits with [`INVALID_FILE`] if any had one.
+fn validate(args: &ValidateArguments) {
+    if args.input.is_empty() {
+        eprintln!("Error: `validate` requires at least one `-i`");
+        std::process::exit(1);
+    }
+
+    let opts = ProbeOptions::builder()
+        .max_pixels(args.max_pixels)
+        .max_memory(args.max_memory)
+        .strict(true)
+        .validate_canvas(true)
+        .build();
+
+    let mut invalid = false;
+
+    for path in &args.input {
+        let result = File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| probe(BufReader::new(file), &opts).map_err(|e| e.to_string()));
+
+        let problem = match result {
+            Ok(probe) => probe.canvas.and_then(|canvas| {
+                let mut problems = Vec::new();
+
+                if !canvas.overflow_frames.is_empty() {
+                    problems.push(format!("frames extend past the canvas: {:?}", canvas.overflow_frames));
+                }
+
+                if !canvas.has_keyframe {
+                    problems.push("no frame covers the whole canvas".to_owned());
+                }
+
+                (!problems.is_empty()).then(|| problems.join(", and "))
+            }),
+            Err(e) => Some(e),
+        };
+
+        if let Some(problem) = problem {
+            invalid = true;
+            println!("{}", error_record(path, &problem));
+        }
+    }
+
+    if invalid {
+        std::process::exit(INVALID_FILE);
+    }
+}
+
+/// Writes the requested frame of the input to the output path as a PNG.
+#[cfg_attr(not(feature = "png"), allow(unused_variables))]
+fn extract(args: &ExtractArguments) {
+    require_feature!("png", "extract", true);
+
+    #[cfg(feature = "png")]
+    {
+        let position = match (args.frame, args.at_ms) {
+            (Some(_), Some(_)) => {
+                eprintln!("Error: `--frame` and `--at-ms` both pick the frame to extract, pass only one of them");
+                std::process::exit(1);
+            }
+            (_, Some(millis)) => FramePosition::Millis(millis),
+            (index, None) => FramePosition::Index(index.unwrap_or(0)),
+        };
+
+        let opts = ProbeOptions::builder()
+            .max_pixels(args.max_pixels)
+            .max_memory(args.max_memory)
+            .thumbnail(args.output.clone())
+            .thumbnail_at(position)
+            // nothing after the requested frame is needed, and a timestamp could land on any of them
+            .frame_at(match position {
+                FramePosition::Index(index) => Some(index),
+                FramePosition::Millis(_) => None,
+            })
+            .build();
+
+        let probe = probe(open(&args.input), &opts).expect_path(&args.input, "extracting the frame");
+
+        if probe.frameless {
+            eprintln!("Error: there's no frame to extract in file: {}", args.input.display());
+            std::process::exit(1);
+        }
+
+        if let FramePosition::Index(index) = position {
+            if probe.frames <= index {
+                eprintln!(
+                    "Error: there's no frame {index} in file, only {}: {}",
+                    probe.frames,
+                    args.input.display()
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The `probe` subcommand, and everything given without a subcommand
+fn probe_files(args: Arguments) {
+    require_feature!("png", "--thumbnail", args.thumbnail.is_some());
+    require_feature!("hash", "--hash", args.hash.is_some());
+    require_feature!("hash", "--key-by-hash", args.key_by_hash);
+    require_feature!("mmap", "--mmap", args.mmap);
+    require_feature!("watch", "--watch", args.watch.is_some());
+    require_feature!("msgpack", "--format msgpack", args.format == Format::MessagePack);
+    require_feature!("cbor", "--format cbor", args.format == Format::Cbor);
+
+    if args.files_from.is_some() && args.paths_from_stdin {
+        eprintln!("Error: `--paths-from-stdin` is the same as `--files-from -`, pass only one of them");
+        std::process::exit(1);
+    }
+
+    if args.key_by_hash && (args.hash.is_none() || args.path_list().is_none() || args.format != Format::Json) {
+        eprintln!("Error: `--key-by-hash` requires `--hash sha256` and `--files-from`, with JSON output");
+        std::process::exit(1);
+    }
+
+    if args.key_by_hash && args.jobs != 1 {
+        eprintln!("Error: `--key-by-hash` keeps the first listed file with given contents, so can't use `--jobs`");
+        std::process::exit(1);
+    }
+
+    if args.pretty && (args.path_list().is_some() || args.recursive || args.watch.is_some() || args.stdio_server) {
+        eprintln!(
+            "Error: `--pretty` output spans several lines, so it can't be used where each result is one line"
+        );
+        std::process::exit(1);
+    }
+
+    if args.output.is_some() && (args.watch.is_some() || args.stdio_server) {
+        eprintln!(
+            "Error: `--output` is replaced once probing is done, which `--watch` and `--stdio-server` never are"
+        );
+        std::process::exit(1);
+    }
+
+    if args.timeout_ms.is_some() && (args.input.len() != 1 || args.recursive) {
+        eprintln!("Error: `--timeout-ms` bounds probing a single file, so it only applies to a single `-i` input");
+        std::process::exit(1);
+    }
+
+    #[cfg(feature = "watch")]
+    if let Some(ref dir) = args.watch {
+        if !args.input.is_empty() || args.path_list().is_some() || args.stdio_server {
+            eprintln!("Error: `--watch` can't be combined with `-i`, `--files-from` or `--stdio-server`");
+            std::process::exit(1);
+        }
+
+        if args.format.single_file() {
+            eprintln!("Error: {SINGLE_FILE_FORMATS} can't be combined with `--watch`");
+            std::process::exit(1);
+        }
+
+        watch(&args, dir);
+    }
+
+    if args.stdio_server {
+        if !args.input.is_empty() || args.path_list().is_some() {
+            eprintln!("Error: `--stdio-server` reads requests from stdin, so it can't be combined with `-i`");
+            std::process::exit(1);
+        }
+
+        if args.format != Format::Json {
+            eprintln!("Error: `--stdio-server` only answers in JSON, so it can't be combined with `--format`");
+            std::process::exit(1);
+        }
+
+        return serve_stdio(&args.probe_options(), &args.json_options(), false);
+    }
+
+    if let Some(list) = args.path_list() {
+        if !args.input.is_empty() {
+            eprintln!("Error: `--files-from` reads paths from a list, so it can't be combined with `-i`");
+            std::process::exit(1);
+        }
+
+        if args.format.single_file() {
+            eprintln!("Error: {SINGLE_FILE_FORMATS} can't be combined with `--files-from`");
+            std::process::exit(1);
+        }
+
+        let mut out = Output::new(&args);
+
+        #[cfg(feature = "hash")]
+        let code = match args.key_by_hash {
+            true => probe_paths_by_hash(&args, &mut out, list),
+            false => probe_paths(&args, &mut out, list_paths(list), None),
+        };
+
+        #[cfg(not(feature = "hash"))]
+        let code = probe_paths(&args, &mut out, list_paths(list), None);
+
+        out.finish();
+
+        if let Some(code) = code {
+            std::process::exit(code);
+        }
+
+        return;
+    }
+
+    if args.per_frame && args.detail_frames.is_some() {
+        eprintln!(
+            "Error: `--per-frame` already reports every frame, so it can't be combined with `--detail-frames`"
+        );
+        std::process::exit(1);
+    }
+
+    if args.per_frame && (args.input.len() != 1 || args.recursive || args.format != Format::Json || args.pretty) {
+        eprintln!("Error: `--per-frame` only applies to probing a single file with the default JSON output");
+        std::process::exit(1);
+    }
+
+    if args.glob.is_some() && !args.recursive {
+        eprintln!("Error: `--glob` only applies to `--recursive`");
+        std::process::exit(1);
+    }
+
+    if args.recursive {
+        let [ref dir] = args.input[..] else {
+            eprintln!("Error: `--recursive` requires a single `-i` naming a directory");
+            std::process::exit(1);
+        };
+
+        if !dir.is_dir() {
+            eprintln!(
+                "Error: `--recursive` requires `-i` to name a directory, not {}",
+                dir.display()
+            );
+            std::process::exit(1);
+        }
+
+        if args.format.single_file() {
+            eprintln!("Error: {SINGLE_FILE_FORMATS} can't be combined with `--recursive`");
+            std::process::exit(1);
+        }
+
+        let files = walk_dir(dir, args.glob.as_deref());
+
+        let mut out = Output::new(&args);
+        let code = probe_paths(&args, &mut out, files.into_iter(), Some(dir));
+        out.finish();
+
+        if let Some(code) = code {
+            std::process::exit(code);
+        }
+
+        return;
+    }
+
+    if args.input.len() > 1 {
+        if args.input.iter().any(|path| path.as_os_str() == "-") {
+            eprintln!("Error: stdin can only be probed on its own, not as one of several `-i` inputs");
+            std::process::exit(1);
+        }
+
+        if args.format.single_file() {
+            eprintln!("Error: {SINGLE_FILE_FORMATS} can't be combined with several `-i` inputs");
+            std::process::exit(1);
+        }
+
+        let mut out = Output::new(&args);
+        let code = probe_inputs(&args, &mut out);
+        out.finish();
+
+        if let Some(code) = code {
+            std::process::exit(code);
+        }
+
+        return;
+    }
+
+    let [ref path] = args.input[..] else {
+        eprintln!("Error: `-i` is required, unless reading paths with `--files-from`");
+        std::process::exit(1);
+    };
+
+    if args.mmap && path.as_os_str() == "-" {
+        eprintln!("Error: `--mmap` only works with files, not stdin");
+        std::process::exit(1);
+    }
+
+    let opts = args.probe_options();
+    let watchdog = args.timeout_ms.map(|millis| Watchdog::start(path, millis));
+
+    // stdin is only limited as it's read
+    let size = match path.as_os_str() == "-" {
+        true => Ok(()),
+        false => opts.check_file_size(std::fs::metadata(path).expect_path(path, "reading file metadata").len()),
+    };
+
+    #[cfg(feature = "mmap")]
+    let probe = size.and_then(|()| match args.mmap {
+        true => probe(&map_file(path).expect_path(path, "mapping file")[..], &opts),
+        false => probe(open(path), &opts),
+    });
+
+    #[cfg(not(feature = "mmap"))]
+    let probe = size.and_then(|()| probe(open(path), &opts));
+
+    if let Some(watchdog) = watchdog {
+        watchdog.finish();
+    }
+
+    if let Err(ref e) = probe {
+        if let Some(rejected) = e.rejection() {
+            reject(&args, path, rejected, e);
+        }
+    }
+
+    let mut probe = probe.expect_path(path, "probing the GIF");
+
+    // only once probing succeeded, so a failure doesn't leave a temporary file behind
+    let mut out = Output::new(&args);
+
+    if args.per_frame {
+        for (index, frame) in probe.frames_detail.take().unwrap_or_default().iter().enumerate() {
+            out.line(frame.to_json_record(index as u64, &args.json_options()));
+        }
+    }
+
+    match args.format {
+        Format::Json => out.line(probe.to_json(&args.json_options())),
+        Format::Oneline => out.line(probe.to_oneline()),
+        Format::Headers => out.raw(probe.to_headers().as_bytes()),
+        Format::Csv => out.line(format_args!(
+            "{}\n{}",
+            GifProbe::CSV_HEADER,
+            probe.to_csv_record(path, &args.json_options())
+        )),
+        Format::BinaryHeader => out.raw(&probe.to_binary_header()),
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack => out.raw(&probe.to_msgpack(&args.json_options())),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => out.raw(&probe.to_cbor(&args.json_options())),
+        #[cfg(not(all(feature = "msgpack", feature = "cbor")))]
+        _ => unreachable!("formats of features that aren't enabled were rejected by `require_feature`"),
+    }
+
+    out.finish();
+
+    if let Some(code) = args.check(path, &probe) {
+        std::process::exit(code);
+    }
 }
@@ -0,0 +1,867 @@
+//! Serialization of [`GifProbe`] into the supported output formats.
+
+use std::{borrow::Cow, fmt, path::Path, time::Duration};
+
+use gif::DisposalMethod;
+use serde::{Serialize, Serializer};
+
+use crate::{
+    scan::join_sub_blocks, AlphaExplanation, AppExtension, Classification, Codec, Comment, FrameAt, FrameDetail,
+    GifProbe, ImageFormat, PaletteSource, UiDetection, Version,
+};
+
+/// How [`GifProbe::to_json`] writes its output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// Number of decimal places floats are rounded to
+    pub float_precision: u32,
+    /// Write every optional field, as `null` when it wasn't collected, so the output always has the same keys
+    pub normalize: bool,
+    /// Indent the output over several lines for people to read, rather than using a single line
+    pub pretty: bool,
+    /// Unit of `duration` and the other summary delays, per-frame delays stay as encoded
+    pub duration_unit: DurationUnit,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions {
+            float_precision: 3,
+            normalize: false,
+            pretty: false,
+            duration_unit: DurationUnit::Centiseconds,
+        }
+    }
+}
+
+/// Unit [`JsonOptions::duration_unit`] converts durations to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DurationUnit {
+    /// What GIF stores delays in
+    #[default]
+    Centiseconds,
+    Milliseconds,
+    /// Written as floats
+    Seconds,
+}
+
+impl DurationUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DurationUnit::Centiseconds => "cs",
+            DurationUnit::Milliseconds => "ms",
+            DurationUnit::Seconds => "s",
+        }
+    }
+
+    /// `centiseconds` converted to this unit
+    pub fn convert(self, centiseconds: f64) -> f64 {
+        match self {
+            DurationUnit::Centiseconds => centiseconds,
+            DurationUnit::Milliseconds => centiseconds * 10.0,
+            DurationUnit::Seconds => centiseconds / 100.0,
+        }
+    }
+}
+
+impl std::str::FromStr for DurationUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cs" => Ok(DurationUnit::Centiseconds),
+            "ms" => Ok(DurationUnit::Milliseconds),
+            "s" => Ok(DurationUnit::Seconds),
+            _ => Err(format!("unknown duration unit `{s}`, expected `cs`, `ms` or `s`")),
+        }
+    }
+}
+
+/// Whole centiseconds in a [`DurationUnit`], staying integers unless that's seconds
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Time {
+    Whole(u64),
+    Seconds(f64),
+}
+
+impl Time {
+    fn new(centiseconds: u64, unit: DurationUnit, float_precision: u32) -> Self {
+        match unit {
+            DurationUnit::Centiseconds => Time::Whole(centiseconds),
+            DurationUnit::Milliseconds => Time::Whole(centiseconds * 10),
+            DurationUnit::Seconds => Time::Seconds(round(centiseconds as f64 / 100.0, float_precision)),
+        }
+    }
+}
+
+/// What [`GifProbe::to_json`] writes, in order, with the [`JsonOptions`] already applied.
+///
+/// The core fields are always there. Each later group of fields is `None` to leave it out, unless it was
+/// collected or `normalize` is set, in which case missing values are written as nulls.
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<Cow<'a, str>>,
+    alpha: bool,
+    alpha_coverage: Option<f64>,
+    max_colors: u16,
+    duration: Time,
+    duration_unit: &'static str,
+    duration_normalized: Time,
+    frames: u64,
+    min_delay: Option<Time>,
+    max_delay: Option<Time>,
+    mean_delay: Option<f64>,
+    fps: Option<f64>,
+    animated: bool,
+    width: u16,
+    height: u16,
+    format: &'static str,
+    // the only compression GIF has, for uniformity with other formats' probes
+    codec: &'static str,
+    version: &'static str,
+    background_index: u8,
+    background_color: Option<String>,
+    pixel_aspect_ratio: Option<f64>,
+    first_frame_full: bool,
+    first_frame_standalone: bool,
+    first_frame_palette_source: &'static str,
+    first_frame_dispose: Option<&'static str>,
+    first_frame_transparent_index: Option<u8>,
+    any_later_transparent_index: bool,
+    safe_first_frame_preview: bool,
+    frameless: bool,
+    has_color_profile: bool,
+    loop_count: Option<u16>,
+    has_xmp: bool,
+    bytes_read: u64,
+    file_bytes_read: u64,
+    memory_peak: u64,
+    expansion_ratio: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trailing_bytes: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    polyglot_suspect: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette_changes: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distinct_palettes: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    used_colors: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette_utilization: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_simultaneous_colors: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grayscale: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_lzw_code_width: Option<Option<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warnings: Option<&'a [&'static str]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_frame_bit_depth: Option<Option<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zero_delay_frames: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_zero_delays: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    longest_frame_index: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    longest_frame_delay: Option<Option<Time>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dominant_color: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_color: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dominant_colors: Option<Option<Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blurhash: Option<Option<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phash: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alpha_explanation: Option<Option<AlphaExplanation>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rendered_alpha: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rendered_alpha_frame: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fps_timeline: Option<Option<Vec<Option<f64>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames_detail: Option<Option<&'a [FrameDetail]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame_at: Option<Option<&'a FrameAt>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_extensions: Option<Option<&'a [AppExtension]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comments: Option<Option<&'a [Comment]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xmp: Option<Option<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame_offsets: Option<Option<&'a [u64]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_class: Option<Option<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_scores: Option<Option<Classification>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ui_animation: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ui_factors: Option<Option<&'a UiDetection>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    should_be_static: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effectively_static: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_frames: Option<Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canvas_valid: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canvas_overflow_frames: Option<Option<&'a [u64]>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_keyframe: Option<Option<bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decode_ms: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    header_ms: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_frame_ms: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_frames_ms: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_per_sec: Option<Option<f64>>,
+}
+
+/// The same fields as [`GifProbe::to_json`] with the default [`JsonOptions`], to embed a probe in other output.
+impl Serialize for GifProbe {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.record(None, &JsonOptions::default()).serialize(serializer)
+    }
+}
+
+impl GifProbe {
+    pub fn to_json(&self, options: &JsonOptions) -> String {
+        to_json(&self.record(None, options), options.pretty)
+    }
+
+    /// Same fields as [`to_json`](Self::to_json), as MessagePack. `pretty` is ignored.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self, options: &JsonOptions) -> Vec<u8> {
+        rmp_serde::to_vec_named(&self.record(None, options)).expect("probes can be written as MessagePack")
+    }
+
+    /// Same fields as [`to_json`](Self::to_json), as CBOR. `pretty` is ignored.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self, options: &JsonOptions) -> Vec<u8> {
+        let mut out = Vec::new();
+        ciborium::into_writer(&self.record(None, options), &mut out).expect("probes can be written as CBOR");
+        out
+    }
+
+    /// Same as [`to_json`](Self::to_json) with a leading `path` field, for one line of many files' results.
+    pub fn to_json_record(&self, path: &Path, options: &JsonOptions) -> String {
+        to_json(&self.record(Some(path), options), options.pretty)
+    }
+
+    /// Picks the core fields, then each optional group of fields if it was collected,
+    /// or as nulls when `normalize` is set.
+    fn record<'a>(&'a self, path: Option<&'a Path>, options: &JsonOptions) -> Record<'a> {
+        let (normalize, unit, precision) = (options.normalize, options.duration_unit, options.float_precision);
+
+        let float = |value: f64| value.is_finite().then(|| round(value, precision));
+        let time = |cs: u64| Time::new(cs, unit, precision);
+        let delay = |cs: u16| time(cs as u64);
+
+        #[cfg(feature = "hash")]
+        let sha256 = self.sha256_hex();
+        #[cfg(not(feature = "hash"))]
+        let sha256: Option<String> = None;
+
+        let trailing = normalize || self.trailing_bytes.is_some();
+        let palette_stats = normalize || self.palette_stats.is_some();
+        let used_colors = normalize || self.used_colors.is_some();
+        let frame_stats = normalize || self.zero_delay_frames.is_some();
+        let rendered_alpha = normalize || self.rendered_alpha.is_some();
+        let classification = normalize || self.classification.is_some();
+        let ui_detection = normalize || self.ui_detection.is_some();
+        let static_suggestion = normalize || self.static_suggestion.is_some();
+        let canvas = normalize || self.canvas.is_some();
+        let timing = normalize || self.timing.is_some();
+
+        let stats = self.palette_stats.as_ref();
+        let suggestion = self.static_suggestion.as_ref();
+        let canvas_check = self.canvas.as_ref();
+        let millis = |d: Duration| float(d.as_secs_f64() * 1000.0);
+
+        Record {
+            path: path.map(Path::to_string_lossy),
+            alpha: self.alpha,
+            alpha_coverage: self.alpha_coverage.and_then(float),
+            max_colors: self.max_colors,
+            duration: time(self.duration),
+            duration_unit: unit.as_str(),
+            duration_normalized: time(self.duration_normalized),
+            frames: self.frames,
+            min_delay: self.min_delay.map(delay),
+            max_delay: self.max_delay.map(delay),
+            mean_delay: self.mean_delay().and_then(|mean| float(unit.convert(mean))),
+            fps: self.fps().and_then(float),
+            animated: self.animated(),
+            width: self.width,
+            height: self.height,
+            format: self.format.as_str(),
+            codec: self.codec.as_str(),
+            version: self.version.as_str(),
+            background_index: self.background_index,
+            background_color: self.background_color.map(hex_color),
+            pixel_aspect_ratio: self.pixel_aspect_ratio.and_then(float),
+            first_frame_full: self.first_frame_full,
+            first_frame_standalone: self.first_frame_standalone,
+            first_frame_palette_source: self.first_frame_palette_source.as_str(),
+            first_frame_dispose: self.first_frame_dispose.map(dispose_name),
+            first_frame_transparent_index: self.first_frame_transparent_index,
+            any_later_transparent_index: self.any_later_transparent_index,
+            safe_first_frame_preview: self.safe_first_frame_preview,
+            frameless: self.frameless,
+            has_color_profile: self.has_color_profile,
+            loop_count: self.loop_count,
+            has_xmp: self.has_xmp,
+            bytes_read: self.bytes_read,
+            file_bytes_read: self.file_bytes_read,
+            memory_peak: self.memory_peak,
+            expansion_ratio: self.expansion_ratio().and_then(float),
+
+            sha256: (normalize || sha256.is_some()).then_some(sha256),
+            trailing_bytes: trailing.then_some(self.trailing_bytes),
+            polyglot_suspect: trailing.then_some(self.polyglot_suspect),
+            palette_changes: palette_stats.then(|| stats.map(|s| s.palette_changes)),
+            distinct_palettes: palette_stats.then(|| stats.map(|s| s.distinct_palettes)),
+            used_colors: used_colors.then_some(self.used_colors),
+            palette_utilization: used_colors.then(|| self.palette_utilization().and_then(float)),
+            max_simultaneous_colors: (normalize || self.max_simultaneous_colors.is_some())
+                .then_some(self.max_simultaneous_colors),
+            grayscale: (normalize || self.grayscale.is_some()).then_some(self.grayscale),
+            max_lzw_code_width: (normalize || self.max_lzw_code_width.is_some())
+                .then_some(self.max_lzw_code_width),
+            warnings: (normalize || !self.warnings.is_empty()).then_some(&self.warnings),
+            first_frame_bit_depth: (normalize || self.first_frame_bit_depth.is_some())
+                .then_some(self.first_frame_bit_depth),
+            zero_delay_frames: frame_stats.then_some(self.zero_delay_frames),
+            all_zero_delays: frame_stats.then(|| self.all_zero_delays()),
+            longest_frame_index: frame_stats.then(|| self.longest_frame.map(|(index, _)| index)),
+            longest_frame_delay: frame_stats.then(|| self.longest_frame.map(|(_, cs)| delay(cs))),
+            dominant_color: (normalize || self.dominant_color.is_some())
+                .then(|| self.dominant_color.map(hex_color)),
+            average_color: (normalize || self.average_color.is_some())
+                .then(|| self.average_color.flatten().map(hex_color)),
+            dominant_colors: (normalize || self.dominant_colors.is_some()).then(|| {
+                self.dominant_colors.as_ref().map(|colors| colors.iter().copied().map(hex_color).collect())
+            }),
+            blurhash: (normalize || self.blurhash.is_some()).then_some(self.blurhash.as_deref()),
+            phash: (normalize || self.phash.is_some()).then(|| self.phash.map(|hash| format!("{hash:016x}"))),
+            alpha_explanation: (normalize || self.alpha_explanation.is_some()).then_some(self.alpha_explanation),
+            rendered_alpha: rendered_alpha.then(|| self.rendered_alpha.map(|frame| frame.is_some())),
+            rendered_alpha_frame: rendered_alpha.then(|| self.rendered_alpha.flatten()),
+            fps_timeline: (normalize || self.fps_timeline.is_some()).then(|| {
+                self.fps_timeline.as_ref().map(|fps| fps.iter().map(|fps| fps.and_then(float)).collect())
+            }),
+            frames_detail: (normalize || self.frames_detail.is_some()).then_some(self.frames_detail.as_deref()),
+            truncated: (normalize || self.truncated.is_some()).then_some(self.truncated),
+            frame_at: (normalize || self.frame_at.is_some())
+                .then(|| self.frame_at.as_ref().and_then(Option::as_ref)),
+            app_extensions: (normalize || self.app_extensions.is_some()).then_some(self.app_extensions.as_deref()),
+            comments: (normalize || self.comments.is_some()).then_some(self.comments.as_deref()),
+            xmp: (normalize || self.xmp.is_some()).then(|| self.xmp.as_ref().and_then(|xmp| xmp.as_deref())),
+            frame_offsets: (normalize || self.frame_offsets.is_some()).then_some(self.frame_offsets.as_deref()),
+            content_class: classification.then(|| self.classification.map(|c| c.content_class.as_str())),
+            content_scores: classification.then(|| {
+                self.classification.map(|c| Classification {
+                    partial_frame_ratio: round(c.partial_frame_ratio, precision),
+                    delay_variation: round(c.delay_variation, precision),
+                    ..c
+                })
+            }),
+            ui_animation: ui_detection.then(|| self.ui_detection.map(|ui| ui.ui_animation)),
+            ui_factors: ui_detection.then_some(self.ui_detection.as_ref()),
+            should_be_static: static_suggestion.then(|| suggestion.map(|s| s.should_be_static)),
+            effectively_static: static_suggestion.then(|| suggestion.map(|s| s.effectively_static)),
+            duplicate_frames: static_suggestion.then(|| suggestion.map(|s| s.duplicate_frames)),
+            canvas_valid: canvas.then(|| canvas_check.map(|c| c.valid)),
+            canvas_overflow_frames: canvas.then(|| canvas_check.map(|c| &*c.overflow_frames)),
+            has_keyframe: canvas.then(|| canvas_check.map(|c| c.has_keyframe)),
+            decode_ms: timing.then(|| self.timing.and_then(|t| millis(t.total))),
+            header_ms: timing.then(|| self.timing.and_then(|t| t.header).and_then(millis)),
+            first_frame_ms: timing.then(|| self.timing.and_then(|t| t.first_frame).and_then(millis)),
+            remaining_frames_ms: timing.then(|| self.timing.and_then(|t| t.remaining_frames).and_then(millis)),
+            bytes_per_sec: timing.then(|| self.bytes_per_sec().and_then(float)),
+        }
+    }
+
+    /// [`bytes_read`](Self::bytes_read) over the total of [`timing`](Self::timing), `None` if it wasn't
+    /// requested or took no measurable time
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let secs = self.timing?.total.as_secs_f64();
+        (secs > 0.0).then(|| self.bytes_read as f64 / secs)
+    }
+
+    /// [`used_colors`](Self::used_colors) as a fraction of [`max_colors`](Self::max_colors),
+    /// `None` if either is missing
+    pub fn palette_utilization(&self) -> Option<f64> {
+        self.used_colors.filter(|_| self.max_colors > 0).map(|used| used as f64 / self.max_colors as f64)
+    }
+
+    /// Average frame delay, `None` without frames
+    pub fn mean_delay(&self) -> Option<f64> {
+        (self.frames > 0).then(|| self.duration as f64 / self.frames as f64)
+    }
+
+    /// Average frames per second over the whole animation, `None` if it has no duration
+    pub fn fps(&self) -> Option<f64> {
+        (self.duration > 0).then(|| self.frames as f64 * 100.0 / self.duration as f64)
+    }
+
+    /// Pixels of every frame at the size of the logical screen per byte read, `None` if nothing was read.
+    /// A small file decoding into a lot of pixel data has a large ratio.
+    pub fn expansion_ratio(&self) -> Option<f64> {
+        let pixels = self.width as f64 * self.height as f64 * self.frames as f64;
+        (self.bytes_read > 0).then(|| pixels / self.bytes_read as f64)
+    }
+
+    /// Whether there's more than one frame and any time passes between them, so zero-delay frames
+    /// stacked on top of each other count as a static image
+    pub fn animated(&self) -> bool {
+        self.frames > 1 && self.duration > 0
+    }
+
+    /// Whether every frame of an animation has a delay of zero, which players render inconsistently,
+    /// `None` if [`zero_delay_frames`](Self::zero_delay_frames) is missing
+    pub fn all_zero_delays(&self) -> Option<bool> {
+        self.zero_delay_frames.map(|zero| self.frames > 1 && zero == self.frames)
+    }
+
+    /// [`sha256`](Self::sha256) as lowercase hex
+    #[cfg(feature = "hash")]
+    pub fn sha256_hex(&self) -> Option<String> {
+        use std::fmt::Write;
+
+        self.sha256.map(|digest| {
+            digest.iter().fold(String::with_capacity(64), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+        })
+    }
+
+    /// Column names for [`to_csv_record`](Self::to_csv_record) and [`csv_error_record`], in order.
+    pub const CSV_HEADER: &'static str = concat!(
+        "path,alpha,alpha_coverage,max_colors,duration,duration_unit,duration_normalized,frames,min_delay,",
+        "max_delay,mean_delay,fps,animated,width,height,version,background_index,background_color,",
+        "pixel_aspect_ratio,first_frame_full,first_frame_standalone,first_frame_palette_source,",
+        "first_frame_dispose,first_frame_transparent_index,any_later_transparent_index,",
+        "safe_first_frame_preview,frameless,has_color_profile,loop_count,has_xmp,bytes_read,error",
+    );
+
+    /// One row of the core fields under [`CSV_HEADER`](Self::CSV_HEADER), with an empty `error`.
+    /// Floats are rounded and durations converted like in [`to_json`](Self::to_json),
+    /// the other options don't apply.
+    pub fn to_csv_record(&self, path: &Path, options: &JsonOptions) -> String {
+        let unit = options.duration_unit;
+
+        let float = |value: Option<f64>| {
+            value.map_or(String::new(), |value| round(value, options.float_precision).to_string())
+        };
+
+        // integers like in the JSON, unless they're in seconds
+        let time = |cs: u64| match unit {
+            DurationUnit::Seconds => float(Some(unit.convert(cs as f64))),
+            _ => (unit.convert(cs as f64) as u64).to_string(),
+        };
+
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},",
+            csv_field(&path.to_string_lossy()),
+            self.alpha,
+            float(self.alpha_coverage),
+            self.max_colors,
+            time(self.duration),
+            unit.as_str(),
+            time(self.duration_normalized),
+            self.frames,
+            self.min_delay.map_or(String::new(), |delay| time(delay as u64)),
+            self.max_delay.map_or(String::new(), |delay| time(delay as u64)),
+            float(self.mean_delay().map(|mean| unit.convert(mean))),
+            float(self.fps()),
+            self.animated(),
+            self.width,
+            self.height,
+            self.version.as_str(),
+            self.background_index,
+            self.background_color.map_or(String::new(), hex_color),
+            float(self.pixel_aspect_ratio),
+            self.first_frame_full,
+            self.first_frame_standalone,
+            self.first_frame_palette_source.as_str(),
+            self.first_frame_dispose.map_or("", dispose_name),
+            self.first_frame_transparent_index.map_or(String::new(), |index| index.to_string()),
+            self.any_later_transparent_index,
+            self.safe_first_frame_preview,
+            self.frameless,
+            self.has_color_profile,
+            self.loop_count.map_or(String::new(), |count| count.to_string()),
+            self.has_xmp,
+            self.bytes_read,
+        )
+    }
+
+    pub const BINARY_HEADER_VERSION: u16 = 1;
+
+    /// Fixed-size little-endian record of the core fields, see the README for the layout.
+    pub fn to_binary_header(&self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+
+        out[0..4].copy_from_slice(b"GIFP");
+        out[4..6].copy_from_slice(&Self::BINARY_HEADER_VERSION.to_le_bytes());
+        out[6..8].copy_from_slice(&(self.alpha as u16).to_le_bytes());
+        out[8..10].copy_from_slice(&self.max_colors.to_le_bytes());
+        out[10..12].copy_from_slice(&self.width.to_le_bytes());
+        out[12..14].copy_from_slice(&self.height.to_le_bytes());
+        // 14..16 reserved, keeps the 64-bit fields aligned
+        out[16..24].copy_from_slice(&self.frames.to_le_bytes());
+        out[24..32].copy_from_slice(&self.duration.to_le_bytes());
+
+        out
+    }
+
+    /// `Key: Value` lines of the core fields, ready to be passed along as HTTP headers.
+    pub fn to_headers(&self) -> String {
+        format!(
+            concat!(
+                "X-Gif-Alpha: {}\n",
+                "X-Gif-Max-Colors: {}\n",
+                "X-Gif-Duration: {}\n",
+                "X-Gif-Frames: {}\n",
+                "X-Gif-Width: {}\n",
+                "X-Gif-Height: {}\n",
+            ),
+            self.alpha, self.max_colors, self.duration, self.frames, self.width, self.height
+        )
+    }
+
+    /// Terse summary meant for people and `grep`, not for parsing.
+    pub fn to_oneline(&self) -> String {
+        format!(
+            "{}x{} {}f {}cs alpha={} colors={}",
+            self.width, self.height, self.frames, self.duration, self.alpha as u8, self.max_colors
+        )
+    }
+}
+
+/// `{"path": ..., "error": ...}` line for a file that couldn't be probed, alongside [`GifProbe::to_json_record`].
+pub fn error_record(path: &Path, error: &dyn fmt::Display) -> String {
+    ErrorRecord::new(Some(path), None, error).to_json()
+}
+
+/// `{"path": ..., "rejected": ..., "error": ...}` line for a file over a limit, with the reason from
+/// [`ProbeError::rejection`](crate::ProbeError::rejection), and without `path` when probing a single input.
+pub fn rejected_record(path: Option<&Path>, rejected: &str, error: &dyn fmt::Display) -> String {
+    ErrorRecord::new(path, Some(rejected), error).to_json()
+}
+
+/// Object written in place of a probe that failed, see [`error_record`] and [`rejected_record`]
+#[derive(Serialize)]
+pub(crate) struct ErrorRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rejected: Option<&'a str>,
+    error: String,
+}
+
+impl<'a> ErrorRecord<'a> {
+    pub(crate) fn new(path: Option<&'a Path>, rejected: Option<&'a str>, error: &dyn fmt::Display) -> Self {
+        ErrorRecord {
+            path: path.map(Path::to_string_lossy),
+            rejected,
+            error: error.to_string(),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        to_json(self, false)
+    }
+}
+
+/// Writes `value` on one line, or indented over several when `pretty` is set.
+fn to_json(value: &impl Serialize, pretty: bool) -> String {
+    match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
+    }
+    .expect("output is always valid JSON")
+}
+
+/// Rounds `value` to `precision` decimal places, the way floats are written.
+fn round(value: f64, precision: u32) -> f64 {
+    match precision {
+        // beyond this f64 doesn't have the digits anyway
+        17.. => value,
+        // dividing back by a power of ten yields the double closest to the rounded
+        // decimal, which is then printed without trailing noise
+        precision => {
+            let scale = 10f64.powi(precision as i32);
+            (value * scale).round() / scale
+        }
+    }
+}
+
+/// CSV row for a file that couldn't be probed, with only `path` and `error` filled in.
+pub fn csv_error_record(path: &Path, error: &dyn fmt::Display) -> String {
+    let empty = ",".repeat(GifProbe::CSV_HEADER.matches(',').count() - 1);
+
+    format!(
+        "{}{empty},{}",
+        csv_field(&path.to_string_lossy()),
+        csv_field(&error.to_string())
+    )
+}
+
+/// Quotes `value` if it has anything CSV readers would split on, doubling any quotes inside.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")).into(),
+        false => value.into(),
+    }
+}
+
+/// What [`AlphaExplanation`] is written as, with a sentence for people alongside the details
+#[derive(Serialize)]
+struct Explanation {
+    method: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transparent_pixels: Option<u64>,
+    reason: Cow<'static, str>,
+}
+
+impl Serialize for AlphaExplanation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let explanation = match *self {
+            AlphaExplanation::None => Explanation {
+                method: "none",
+                frame: None,
+                transparent_pixels: None,
+                reason: concat!(
+                    "the first frame has no transparent pixels, ",
+                    "and every area cleared by a later frame's disposal is repainted"
+                )
+                .into(),
+            },
+            AlphaExplanation::FirstFramePixels { transparent_pixels } => Explanation {
+                method: "first_frame_pixels",
+                frame: Some(0),
+                transparent_pixels: Some(transparent_pixels),
+                reason: format!("the first frame has {transparent_pixels} pixels with its transparent color")
+                    .into(),
+            },
+            AlphaExplanation::BackgroundDisposal { frame } => Explanation {
+                method: "background_disposal",
+                frame: Some(frame),
+                transparent_pixels: None,
+                reason: format!(
+                    concat!(
+                        "frame {} is disposed to the background, ",
+                        "clearing an area the next frame doesn't repaint"
+                    ),
+                    frame
+                )
+                .into(),
+            },
+        };
+
+        explanation.serialize(serializer)
+    }
+}
+
+impl FrameDetail {
+    /// `{"index": ..., ...}` line for one frame of many, for `--per-frame`
+    pub fn to_json_record(&self, index: u64, options: &JsonOptions) -> String {
+        #[derive(Serialize)]
+        struct Indexed<'a> {
+            index: u64,
+            #[serde(flatten)]
+            detail: &'a FrameDetail,
+        }
+
+        to_json(&Indexed { index, detail: self }, options.pretty)
+    }
+}
+
+/// [`AppExtension`] identifiers and auth codes, as text with anything that isn't UTF-8 replaced
+pub(crate) fn lossy<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&String::from_utf8_lossy(bytes))
+}
+
+/// [`AppExtension::data`] as its payload in base64
+pub(crate) fn payload<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64(&join_sub_blocks(data)))
+}
+
+pub(crate) fn dispose<S: Serializer>(dispose: &DisposalMethod, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(dispose_name(*dispose))
+}
+
+/// CSS-style `#rrggbb` color
+fn hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+
+    out
+}
+
+impl ImageFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageFormat::Gif => "gif",
+            #[cfg(feature = "webp")]
+            ImageFormat::WebP => "webp",
+            #[cfg(feature = "apng")]
+            ImageFormat::Png => "png",
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => "avif",
+        }
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(ImageFormat::Gif),
+            #[cfg(feature = "webp")]
+            "webp" => Ok(ImageFormat::WebP),
+            #[cfg(feature = "apng")]
+            "png" => Ok(ImageFormat::Png),
+            #[cfg(feature = "avif")]
+            "avif" => Ok(ImageFormat::Avif),
+            _ => Err(match s {
+                "webp" => "image format `webp` requires gif_probe to be built with the `webp` feature".to_owned(),
+                "png" => "image format `png` requires gif_probe to be built with the `apng` feature".to_owned(),
+                "avif" => "image format `avif` requires gif_probe to be built with the `avif` feature".to_owned(),
+                _ => format!("unknown image format `{s}`, expected `gif`, `webp`, `png` or `avif`"),
+            }),
+        }
+    }
+}
+
+impl Codec {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Codec::Lzw => "lzw",
+            Codec::Vp8 => "vp8",
+            Codec::Vp8l => "vp8l",
+            Codec::Deflate => "deflate",
+            Codec::Av1 => "av1",
+        }
+    }
+}
+
+impl Version {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Version::Gif87a => "87a",
+            Version::Gif89a => "89a",
+        }
+    }
+}
+
+impl PaletteSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PaletteSource::Global => "global",
+            PaletteSource::Local => "local",
+            PaletteSource::None => "none",
+        }
+    }
+}
+
+fn dispose_name(dispose: DisposalMethod) -> &'static str {
+    match dispose {
+        DisposalMethod::Any => "any",
+        DisposalMethod::Keep => "keep",
+        DisposalMethod::Background => "background",
+        DisposalMethod::Previous => "previous",
+    }
+}
+
+#[cfg(all(test, any(feature = "msgpack", feature = "cbor")))]
+mod tests {
+    use crate::{probe, GifProbe, JsonOptions, ProbeOptions};
+
+    const TWO_FRAMES: &[u8] = include_bytes!("../tests/fixtures/two_frames.gif");
+
+    /// A probe with nested objects, arrays and floats, and the options to write it with
+    fn sample() -> (GifProbe, JsonOptions) {
+        let options = ProbeOptions {
+            detail_frames: Some(2),
+            frame_at: Some(1),
+            fps_timeline: true,
+            explain_alpha: true,
+            classify: Some(Default::default()),
+            ..Default::default()
+        };
+
+        let json_options = JsonOptions {
+            normalize: true,
+            ..Default::default()
+        };
+
+        (probe(TWO_FRAMES, &options).unwrap(), json_options)
+    }
+
+    fn json(probe: &GifProbe, options: &JsonOptions) -> serde_json::Value {
+        serde_json::from_str(&probe.to_json(options)).unwrap()
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_matches_json() {
+        let (probe, options) = sample();
+        let msgpack: serde_json::Value = rmp_serde::from_slice(&probe.to_msgpack(&options)).unwrap();
+
+        assert_eq!(msgpack, json(&probe, &options));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_matches_json() {
+        let (probe, options) = sample();
+        let cbor: serde_json::Value = ciborium::from_reader(&probe.to_cbor(&options)[..]).unwrap();
+
+        assert_eq!(cbor, json(&probe, &options));
+    }
+}
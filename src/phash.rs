@@ -0,0 +1,83 @@
+//! Perceptual hash of the first frame for `--phash`.
+
+use gif::Frame;
+
+use crate::composite::Canvas;
+
+/// 9 columns by 8 rows, so comparing horizontal neighbors gives exactly 64 bits
+const COLUMNS: usize = 9;
+const ROWS: usize = 8;
+
+/// Draws the first frame onto a canvas the size of the logical screen and computes its difference hash,
+/// with transparent pixels treated as black. `None` if the canvas is empty.
+///
+/// The canvas is shrunk to a 9x8 grid of mean luma by averaging every pixel into its cell, then each bit
+/// is set when a cell is darker than its right neighbor. Re-encoding, rescaling and palette changes barely
+/// move those averages, so the same image hashes the same, or within a few bits of it.
+pub fn dhash(width: u16, height: u16, frame: &Frame, global_palette: Option<&[u8]>) -> Option<u64> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    canvas.draw(frame, global_palette);
+
+    let (width, height) = (width as usize, height as usize);
+    let mut cells = [[(0.0, 0u64); COLUMNS]; ROWS];
+
+    for (y, row) in canvas.pixels().chunks_exact(width * 4).enumerate() {
+        let cells = &mut cells[y * ROWS / height];
+
+        for (x, px) in row.chunks_exact(4).enumerate() {
+            let (sum, count) = &mut cells[x * COLUMNS / width];
+            let alpha = px[3] as f64 / 255.0;
+
+            *sum += (0.299 * px[0] as f64 + 0.587 * px[1] as f64 + 0.114 * px[2] as f64) * alpha;
+            *count += 1;
+        }
+    }
+
+    // screens narrower than the grid leave some cells empty, which count as black
+    let luma = cells.map(|row| row.map(|(sum, count)| if count > 0 { sum / count as f64 } else { 0.0 }));
+
+    Some(
+        luma.iter()
+            .flat_map(|row| row.windows(2))
+            .fold(0, |hash, pair| hash << 1 | (pair[0] < pair[1]) as u64),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame covering the whole canvas, with `pixels` indexing into the global palette
+    fn frame(width: u16, height: u16, pixels: Vec<u8>) -> Frame<'static> {
+        Frame {
+            width,
+            height,
+            buffer: pixels.into(),
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn horizontal_gradients() {
+        // 18 grays getting lighter to the right, two columns to a cell
+        let grays: Vec<u8> = (0..18).flat_map(|i| [i * 15; 3]).collect();
+        let lighter = (0..8).flat_map(|_| 0..18).collect();
+        let darker = (0..8).flat_map(|_| (0..18).rev()).collect();
+
+        assert_eq!(dhash(18, 8, &frame(18, 8, lighter), Some(&grays)), Some(u64::MAX));
+        assert_eq!(dhash(18, 8, &frame(18, 8, darker), Some(&grays)), Some(0));
+    }
+
+    #[test]
+    fn screens_smaller_than_the_grid() {
+        // 3x2 white pixels fill columns 0, 3 and 6 of rows 0 and 4, leaving the other cells black, so those rows
+        // only have bits 2 and 5 set, counting from the most significant, and the first row comes first
+        let hash = dhash(3, 2, &frame(3, 2, vec![0; 6]), Some(&[255, 255, 255]));
+
+        assert_eq!(hash, Some(0x2400_0000_2400_0000));
+    }
+}
@@ -0,0 +1,1365 @@
+use std::{
+    cell::Cell,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Read},
+    time::{Duration, Instant},
+};
+
+use gif::{ColorOutput, DecodeOptions, DisposalMethod, Frame, MemoryLimit};
+use serde::Serialize;
+
+use crate::{
+    classify::{Classification, Classifier, UiDetection},
+    colors::{
+        average_color, dominant_color, dominant_colors, ColorSet, GrayscaleCheck, PaletteStats, PaletteTracker,
+        SimultaneousColors,
+    },
+    composite::Canvas,
+    scan::{AppExtension, Comment, LzwWidths, Scan, TrailingData, Violation},
+    ProbeError, ProbeOptions,
+};
+
+#[cfg(feature = "png")]
+use crate::thumbnail::Thumbnail;
+
+#[derive(Default)]
+pub struct GifProbe {
+    pub alpha: bool,
+    /// Fraction of the first frame's pixels with its transparent color, `None` without frames
+    pub alpha_coverage: Option<f64>,
+    pub max_colors: u16,
+    pub duration: u64,
+    /// [`duration`](Self::duration) with delays clamped the way browsers play them back
+    pub duration_normalized: u64,
+    pub frames: u64,
+    /// Shortest and longest frame delays as encoded, `None` without frames
+    pub min_delay: Option<u16>,
+    pub max_delay: Option<u16>,
+    pub width: u16,
+    pub height: u16,
+    /// The container format the input was probed as
+    pub format: ImageFormat,
+    /// Compression of the image data, only ever [`Codec::Lzw`] unless this is a WebP, PNG or AVIF
+    pub codec: Codec,
+    /// Version from the header, extensions like transparency and delays need 89a
+    pub version: Version,
+    /// Background color index from the logical screen descriptor
+    pub background_index: u8,
+    /// The background color from the global palette, `None` without one or if the index is past its end
+    pub background_color: Option<[u8; 3]>,
+    /// Width over height of a pixel from the logical screen descriptor, `None` when unspecified
+    pub pixel_aspect_ratio: Option<f64>,
+
+    /// The first frame covers the whole canvas
+    pub first_frame_full: bool,
+    /// The first frame covers the whole canvas and has no transparent pixels,
+    /// so it can stand in for the animation without compositing later frames
+    pub first_frame_standalone: bool,
+    /// Which palette the first frame's colors come from
+    pub first_frame_palette_source: PaletteSource,
+    /// How the first frame is disposed of before the next one, `None` without frames
+    pub first_frame_dispose: Option<DisposalMethod>,
+    /// Palette index the first frame declares transparent, whether or not any pixel uses it
+    pub first_frame_transparent_index: Option<u8>,
+    /// Any frame after the first declares a transparent index
+    pub any_later_transparent_index: bool,
+    /// The first frame alone is a faithful preview of the animation's start, see the README
+    pub safe_first_frame_preview: bool,
+
+    /// There are no image blocks at all, so nothing would be rendered
+    pub frameless: bool,
+
+    /// An application extension embeds an ICC color profile
+    pub has_color_profile: bool,
+
+    /// Times to play the animation from its loop extension, where 0 means forever, `None` without one
+    pub loop_count: Option<u16>,
+
+    /// An application extension carries an XMP metadata packet
+    pub has_xmp: bool,
+
+    /// Conformance problems that were tolerated, see the README
+    pub warnings: Vec<&'static str>,
+
+    /// Instantaneous FPS of each frame, `None` for zero-delay frames
+    pub fps_timeline: Option<Vec<Option<f64>>>,
+
+    pub canvas: Option<CanvasCheck>,
+
+    /// Bits needed to index the first frame's palette, local or global, when requested
+    pub first_frame_bit_depth: Option<u8>,
+
+    /// Frames with a delay of zero, when requested
+    pub zero_delay_frames: Option<u64>,
+
+    /// Index and delay of the first frame with the longest delay, when requested
+    pub longest_frame: Option<(u64, u16)>,
+
+    /// Approximate most frequent opaque color of the first frame, when requested
+    pub dominant_color: Option<[u8; 3]>,
+
+    /// Mean color of the first frame's opaque pixels when requested, `None` inside if there are none
+    pub average_color: Option<Option<[u8; 3]>>,
+
+    /// Most frequent opaque colors of the first frame, most frequent first, when requested
+    pub dominant_colors: Option<Vec<[u8; 3]>>,
+
+    /// BlurHash of the first frame on the logical screen, when requested and there's anything to encode
+    pub blurhash: Option<String>,
+
+    /// 64-bit perceptual difference hash of the first frame on the logical screen, when requested and there's
+    /// anything to hash
+    pub phash: Option<u64>,
+
+    /// How often frames switch palettes, when requested
+    pub palette_stats: Option<PaletteStats>,
+
+    /// Distinct RGB colors drawn across all frames, not counting transparent pixels, when requested
+    pub used_colors: Option<u32>,
+
+    /// Most distinct colors visible on the composited canvas at once, when requested
+    pub max_simultaneous_colors: Option<u32>,
+
+    /// Whether every color drawn, not counting transparent pixels, has equal red, green and blue, when requested
+    pub grayscale: Option<bool>,
+
+    /// Widest LZW code read in any frame's image data, up to 12 bits, when requested
+    pub max_lzw_code_width: Option<u8>,
+
+    /// Why [`alpha`](Self::alpha) is what it is, when requested
+    pub alpha_explanation: Option<AlphaExplanation>,
+
+    /// Index of the first composited frame with transparent pixels when requested, `None` inside if none has any
+    pub rendered_alpha: Option<Option<u64>>,
+
+    /// Bytes consumed from the input, including whatever the decoder buffered ahead
+    pub bytes_read: u64,
+
+    /// Bytes of the file the walked frames cover: up to the trailer if the whole stream was walked, or to the
+    /// end of the last walked frame's image data if probing stopped early
+    pub file_bytes_read: u64,
+
+    /// Largest frame buffer the decoder allocated, in bytes, which is what the memory limit applies to.
+    /// 0 if only frame headers were needed.
+    pub memory_peak: u64,
+
+    /// SHA-256 of the whole input, when requested
+    #[cfg(feature = "hash")]
+    pub sha256: Option<[u8; 32]>,
+
+    /// Bytes after the trailer, 0 without one, when requested
+    pub trailing_bytes: Option<u64>,
+
+    /// Whether the bytes after the trailer contain a known archive, executable, document or script signature,
+    /// when requested
+    pub polyglot_suspect: Option<bool>,
+
+    /// Byte offset of each frame's image descriptor in the input
+    pub frame_offsets: Option<Vec<u64>>,
+
+    /// Header fields of the first few frames
+    pub frames_detail: Option<Vec<FrameDetail>>,
+
+    /// Application extensions in the order they appear, when requested
+    pub app_extensions: Option<Vec<AppExtension>>,
+
+    /// Comment extensions in the order they appear, when requested
+    pub comments: Option<Vec<Comment>>,
+
+    /// Whether [`ProbeOptions::max_frames`] or [`ProbeOptions::max_duration`] stopped probing with frames left,
+    /// when either is given, or `Some(true)` when [`ProbeIter::finish`](crate::ProbeIter::finish) did
+    pub truncated: Option<bool>,
+
+    /// The XMP packet decoded as UTF-8, when requested, or `Some(None)` if there wasn't one
+    pub xmp: Option<Option<String>>,
+
+    /// The requested frame, when requested, or `Some(None)` if there weren't that many frames
+    pub frame_at: Option<Option<FrameAt>>,
+
+    /// Heuristic content class and the scores behind it, when requested
+    pub classification: Option<Classification>,
+
+    /// Whether this looks like a looping UI asset, when requested
+    pub ui_detection: Option<UiDetection>,
+
+    /// Whether the animation could be a static image, when requested
+    pub static_suggestion: Option<StaticSuggestion>,
+
+    /// How long probing took, when requested
+    pub timing: Option<Timing>,
+}
+
+/// The container formats this build can probe, told apart by their first bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[default]
+    Gif,
+    #[cfg(feature = "webp")]
+    WebP,
+    /// PNG, animated or not
+    #[cfg(feature = "apng")]
+    Png,
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl ImageFormat {
+    /// The format the input starting with `magic` is in, `None` if it's none this build can probe.
+    /// The first 12 bytes are enough to tell them all apart.
+    pub fn sniff(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(b"GIF8") {
+            return Some(ImageFormat::Gif);
+        }
+
+        #[cfg(feature = "webp")]
+        if crate::webp::sniff(magic) {
+            return Some(ImageFormat::WebP);
+        }
+
+        #[cfg(feature = "apng")]
+        if crate::apng::sniff(magic) {
+            return Some(ImageFormat::Png);
+        }
+
+        #[cfg(feature = "avif")]
+        if crate::avif::sniff(magic) {
+            return Some(ImageFormat::Avif);
+        }
+
+        None
+    }
+}
+
+/// How the image data is compressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// The only compression GIF has
+    #[default]
+    Lzw,
+    /// Lossy WebP
+    Vp8,
+    /// Lossless WebP
+    Vp8l,
+    /// PNG, animated or not
+    Deflate,
+    /// AVIF, a still image or an image sequence
+    Av1,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Gif87a,
+    #[default]
+    Gif89a,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteSource {
+    Global,
+    Local,
+    /// Neither a local nor a global palette, or no frame at all
+    #[default]
+    None,
+}
+
+/// Header fields of a single frame
+#[derive(Serialize)]
+pub struct FrameDetail {
+    pub delay: u16,
+    #[serde(serialize_with = "crate::output::dispose")]
+    pub dispose: DisposalMethod,
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    pub transparent: Option<u8>,
+    /// Number of colors in the local palette, if the frame has one
+    pub local_colors: Option<u16>,
+    pub interlaced: bool,
+    /// Bytes of image data in the file, from the LZW minimum code size through the block terminator,
+    /// `None` if probing stopped before the terminator
+    pub compressed_size: Option<u64>,
+}
+
+impl FrameDetail {
+    pub(crate) fn new(frame: &Frame) -> Self {
+        FrameDetail {
+            delay: frame.delay,
+            dispose: frame.dispose,
+            left: frame.left,
+            top: frame.top,
+            width: frame.width,
+            height: frame.height,
+            transparent: frame.transparent,
+            local_colors: frame.palette.as_deref().map(colors),
+            interlaced: frame.interlaced,
+            // filled in from the scanner once the frames are walked
+            compressed_size: None,
+        }
+    }
+}
+
+/// Result of [`ProbeOptions::frame_at`]
+#[derive(Serialize)]
+pub struct FrameAt {
+    pub index: u64,
+    #[serde(flatten)]
+    pub detail: FrameDetail,
+    /// Number of colors in the palette the frame uses, local or global
+    pub colors: u16,
+    /// Whether the frame has pixels with its transparent color index
+    pub transparent_pixels: bool,
+}
+
+impl FrameAt {
+    fn new(index: u64, frame: &Frame, global_palette: Option<&[u8]>) -> Self {
+        FrameAt {
+            index,
+            detail: FrameDetail::new(frame),
+            colors: frame.palette.as_deref().or(global_palette).map_or(0, colors),
+            transparent_pixels: matches!(frame.transparent, Some(tr) if frame.buffer.contains(&tr)),
+        }
+    }
+}
+
+/// Result of [`ProbeOptions::validate_canvas`]
+#[derive(Default)]
+pub struct CanvasCheck {
+    /// No frame overflows the logical screen, and at least one frame covers all of it
+    pub valid: bool,
+    /// Indices of frames extending past the logical screen, up to [`CanvasCheck::MAX_REPORTED`]
+    pub overflow_frames: Vec<u64>,
+    /// At least one frame covers the whole logical screen
+    pub has_keyframe: bool,
+
+    overflows: bool,
+}
+
+impl CanvasCheck {
+    pub const MAX_REPORTED: usize = 16;
+
+    fn check(&mut self, index: u64, frame: &Frame, width: u16, height: u16) {
+        if frame.left as u32 + frame.width as u32 > width as u32
+            || frame.top as u32 + frame.height as u32 > height as u32
+        {
+            self.overflows = true;
+
+            if self.overflow_frames.len() < Self::MAX_REPORTED {
+                self.overflow_frames.push(index);
+            }
+        }
+
+        self.has_keyframe |= covers(frame, width, height);
+        self.valid = !self.overflows && self.has_keyframe;
+    }
+}
+
+/// Result of [`ProbeOptions::explain_alpha`], the first condition that made the GIF count as transparent
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaExplanation {
+    /// No transparency condition was met
+    #[default]
+    None,
+    /// The first frame has pixels with its transparent color index
+    FirstFramePixels { transparent_pixels: u64 },
+    /// A later frame is disposed with [`DisposalMethod::Background`], clearing part of the canvas
+    /// the frame after it doesn't paint over
+    BackgroundDisposal { frame: u64 },
+}
+
+/// Result of [`ProbeOptions::suggest_static`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticSuggestion {
+    /// The animation might as well be a single static image,
+    /// because it's [`effectively_static`](Self::effectively_static) or over 90% duplicate frames
+    pub should_be_static: bool,
+    /// Every composited frame looks exactly like the first one
+    pub effectively_static: bool,
+    /// Frames identical to the frame before them, in position, palette and pixels
+    pub duplicate_frames: u64,
+}
+
+/// Result of [`ProbeOptions::timing`], in wall-clock time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// The whole probe, from the first byte read to the result, including reading the input to EOF
+    pub total: Duration,
+    /// Reading the header, logical screen descriptor and global palette, GIF only
+    pub header: Option<Duration>,
+    /// Reading and decoding the first frame, GIF only
+    pub first_frame: Option<Duration>,
+    /// Walking every frame after the first, GIF only
+    pub remaining_frames: Option<Duration>,
+}
+
+/// Optional analyses fed every frame, on top of what [`GifProbe::add_frame`] collects
+struct Analyses {
+    width: u16,
+    height: u16,
+
+    classifier: Option<Classifier>,
+    palettes: Option<PaletteTracker>,
+
+    // these need every frame decoded in full
+    rendered_alpha: Option<RenderedAlpha>,
+    static_analysis: Option<StaticAnalysis>,
+    used_colors: Option<ColorSet>,
+    simultaneous_colors: Option<SimultaneousColors>,
+    grayscale: Option<GrayscaleCheck>,
+}
+
+impl Analyses {
+    fn new(options: &ProbeOptions, width: u16, height: u16) -> Self {
+        Analyses {
+            width,
+            height,
+            classifier: options.classify.is_some().then(Classifier::default),
+            palettes: options.palette_stats.then(PaletteTracker::default),
+            rendered_alpha: options.exhaustive_alpha.then(|| RenderedAlpha::new(width, height)),
+            static_analysis: options.suggest_static.then(|| StaticAnalysis::new(width, height)),
+            used_colors: options.count_used_colors.then(ColorSet::new),
+            simultaneous_colors: options.max_simultaneous_colors.then(|| SimultaneousColors::new(width, height)),
+            grayscale: options.grayscale.then(GrayscaleCheck::new),
+        }
+    }
+
+    /// Whether frames must be decoded rather than just walked through their headers
+    fn decode_all(&self) -> bool {
+        self.rendered_alpha.is_some()
+            || self.static_analysis.is_some()
+            || self.used_colors.is_some()
+            || self.simultaneous_colors.is_some()
+            || self.grayscale.is_some()
+    }
+
+    fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        if let Some(ref mut classifier) = self.classifier {
+            classifier.add_frame(frame, covers(frame, self.width, self.height));
+        }
+
+        if let Some(ref mut palettes) = self.palettes {
+            palettes.add_frame(frame, global_palette);
+        }
+
+        if let Some(ref mut rendered) = self.rendered_alpha {
+            rendered.add_frame(frame, global_palette);
+        }
+
+        if let Some(ref mut analysis) = self.static_analysis {
+            analysis.add_frame(frame, global_palette);
+        }
+
+        if let Some(ref mut used) = self.used_colors {
+            used.add_frame(frame, global_palette);
+        }
+
+        if let Some(ref mut simultaneous) = self.simultaneous_colors {
+            simultaneous.add_frame(frame, global_palette);
+        }
+
+        if let Some(ref mut grayscale) = self.grayscale {
+            grayscale.add_frame(frame, global_palette);
+        }
+    }
+
+    fn finish(self, probe: &mut GifProbe, options: &ProbeOptions) {
+        probe.rendered_alpha = self.rendered_alpha.map(|rendered| rendered.first_transparent);
+        probe.static_suggestion = self.static_analysis.map(|analysis| analysis.finish(probe.frames));
+        probe.palette_stats = self.palettes.map(PaletteTracker::finish);
+        probe.used_colors = self.used_colors.map(|used| used.len());
+        probe.max_simultaneous_colors = self.simultaneous_colors.map(|simultaneous| simultaneous.max());
+        probe.grayscale = self.grayscale.map(|grayscale| grayscale.is_grayscale());
+
+        if let (Some(classifier), Some(rules)) = (self.classifier, &options.classify) {
+            probe.classification = Some(classifier.finish(probe, rules));
+        }
+    }
+}
+
+/// Composites every frame to find the first one that renders with transparent pixels, which unlike
+/// [`GifProbe::alpha`] accounts for later frames painting over what a disposal cleared.
+struct RenderedAlpha {
+    canvas: Canvas,
+    frames: u64,
+    first_transparent: Option<u64>,
+}
+
+impl RenderedAlpha {
+    fn new(width: u16, height: u16) -> Self {
+        RenderedAlpha {
+            canvas: Canvas::new(width, height),
+            frames: 0,
+            first_transparent: None,
+        }
+    }
+
+    fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        // only the first one is reported
+        if self.first_transparent.is_some() {
+            return;
+        }
+
+        self.canvas.draw(frame, global_palette);
+
+        if self.canvas.pixels().chunks_exact(4).any(|pixel| pixel[3] == 0) {
+            self.first_transparent = Some(self.frames);
+        }
+
+        self.frames += 1;
+    }
+}
+
+/// Composites every frame to tell whether the animation ever actually changes.
+struct StaticAnalysis {
+    canvas: Canvas,
+    first: Option<Vec<u8>>,
+    last_hash: Option<u64>,
+    duplicate_frames: u64,
+    effectively_static: bool,
+}
+
+impl StaticAnalysis {
+    fn new(width: u16, height: u16) -> Self {
+        StaticAnalysis {
+            canvas: Canvas::new(width, height),
+            first: None,
+            last_hash: None,
+            duplicate_frames: 0,
+            effectively_static: true,
+        }
+    }
+
+    fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>) {
+        let mut hasher = DefaultHasher::new();
+        (frame.left, frame.top, frame.width, frame.height).hash(&mut hasher);
+        (frame.transparent, &frame.palette, &frame.buffer).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.last_hash.replace(hash) == Some(hash) {
+            self.duplicate_frames += 1;
+        }
+
+        self.canvas.draw(frame, global_palette);
+
+        match self.first {
+            None => self.first = Some(self.canvas.pixels().to_vec()),
+            Some(ref first) => self.effectively_static &= first[..] == *self.canvas.pixels(),
+        }
+    }
+
+    fn finish(self, frames: u64) -> StaticSuggestion {
+        let mostly_duplicates = frames > 1 && self.duplicate_frames as f64 > 0.9 * (frames - 1) as f64;
+
+        StaticSuggestion {
+            should_be_static: self.effectively_static || mostly_duplicates,
+            effectively_static: self.effectively_static,
+            duplicate_frames: self.duplicate_frames,
+        }
+    }
+}
+
+/// Rectangle of the canvas a frame is drawn to
+#[derive(Clone, Copy)]
+struct Area {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Area {
+    fn of(frame: &Frame) -> Self {
+        Area {
+            left: frame.left as u32,
+            top: frame.top as u32,
+            right: frame.left as u32 + frame.width as u32,
+            bottom: frame.top as u32 + frame.height as u32,
+        }
+    }
+}
+
+/// Whether `frame` paints every pixel of `area` without any transparent ones, judging only by its header,
+/// so a frame declaring a transparent index doesn't count even if it never uses it
+fn repaints(frame: &Frame, area: Area) -> bool {
+    let drawn = Area::of(frame);
+
+    frame.transparent.is_none()
+        && drawn.left <= area.left
+        && drawn.top <= area.top
+        && drawn.right >= area.right
+        && drawn.bottom >= area.bottom
+}
+
+/// Sets [`GifProbe::alpha`] for the frame at `index` being disposed to the background
+fn background_alpha(probe: &mut GifProbe, index: u64, options: &ProbeOptions) {
+    probe.alpha = true;
+
+    if options.explain_alpha {
+        probe.alpha_explanation = Some(AlphaExplanation::BackgroundDisposal { frame: index });
+    }
+}
+
+/// Whether the frame covers the whole canvas
+fn covers(frame: &Frame, width: u16, height: u16) -> bool {
+    frame.left == 0 && frame.top == 0 && frame.width >= width && frame.height >= height
+}
+
+/// Number of colors in a palette, which holds at most 256 of them
+fn colors(palette: &[u8]) -> u16 {
+    (palette.len() / 3) as u16
+}
+
+/// Bits needed to index every color of the palette, `ceil(log2(colors))` within the `1..=8` GIF allows
+fn bit_depth(palette: &[u8]) -> u8 {
+    let colors = colors(palette).max(1) as u32;
+    (u32::BITS - (colors - 1).leading_zeros()).clamp(1, 8) as u8
+}
+
+/// Chrome, Firefox and Safari all play delays of 0 or 1 centisecond as 10
+fn browser_delay(delay: u16) -> u16 {
+    match delay {
+        0 | 1 => 10,
+        delay => delay,
+    }
+}
+
+/// FPS implied by a delay in centiseconds, as encoded. Zero-delay frames have no meaningful rate.
+fn fps(delay: u16) -> Option<f64> {
+    (delay > 0).then(|| 100.0 / delay as f64)
+}
+
+/// A still image is a single frame shown forever, like a GIF without a graphic control extension
+#[cfg(any(feature = "webp", feature = "apng", feature = "avif"))]
+pub(crate) const STILL: [(u64, u16); 1] = [(1, 0)];
+
+impl GifProbe {
+    /// Counts runs of frames sharing a delay, for formats whose delays are read without walking each frame, so a
+    /// huge claimed frame count doesn't need memory
+    #[cfg(any(feature = "webp", feature = "apng", feature = "avif"))]
+    pub(crate) fn add_delays(&mut self, runs: impl IntoIterator<Item = (u64, u16)>) {
+        for (count, delay) in runs.into_iter().filter(|&(count, _)| count > 0) {
+            self.frames += count;
+            self.duration += delay as u64 * count;
+            self.duration_normalized += browser_delay(delay) as u64 * count;
+            self.min_delay = Some(self.min_delay.map_or(delay, |min| min.min(delay)));
+            self.max_delay = Some(self.max_delay.map_or(delay, |max| max.max(delay)));
+        }
+
+        self.frameless = self.frames == 0;
+    }
+
+    /// Accounting shared by every frame, whether fully decoded or not.
+    fn add_frame(&mut self, frame: &Frame, options: &ProbeOptions) {
+        // frames walked by their header alone have no buffer
+        self.memory_peak = self.memory_peak.max(frame.buffer.len() as u64);
+
+        if let Some(ref mut detail) = self.frames_detail {
+            if self.frames < options.detail_frames.unwrap_or(0) {
+                detail.push(FrameDetail::new(frame));
+            }
+        }
+
+        if let Some(ref mut canvas) = self.canvas {
+            canvas.check(self.frames, frame, self.width, self.height);
+        }
+
+        // ties go to the first frame
+        if options.frame_stats && !matches!(self.longest_frame, Some((_, delay)) if delay >= frame.delay) {
+            self.longest_frame = Some((self.frames, frame.delay));
+        }
+
+        self.frames += 1;
+        self.duration += frame.delay as u64;
+        self.duration_normalized += browser_delay(frame.delay) as u64;
+        self.min_delay = Some(self.min_delay.map_or(frame.delay, |delay| delay.min(frame.delay)));
+        self.max_delay = Some(self.max_delay.map_or(frame.delay, |delay| delay.max(frame.delay)));
+
+        if let Some(ref mut zero_delay_frames) = self.zero_delay_frames {
+            *zero_delay_frames += (frame.delay == 0) as u64;
+        }
+
+        if let Some(ref mut timeline) = self.fps_timeline {
+            timeline.push(fps(frame.delay));
+        }
+
+        if let Some(ref p) = frame.palette {
+            self.max_colors = self.max_colors.max(colors(p));
+        }
+    }
+}
+
+/// Probes the GIF read from `reader`, or with the `webp`, `apng` or `avif` features, the WebP, PNG or AVIF it
+/// starts like, unless [`ProbeOptions::format_hint`] says which it is. Input that starts like none of them is
+/// probed as a GIF, failing to decode.
+///
+/// Only the first frame is decoded in full, the rest are walked through their headers.
+pub fn probe<R: Read>(reader: R, options: &ProbeOptions) -> Result<GifProbe, ProbeError> {
+    probe_frames(reader, options, &mut |_| true)
+}
+
+/// Same as [`probe`], calling `on_frame` with each frame of a GIF before it's counted, and stopping like
+/// [`ProbeOptions::max_frames`] would, without counting the frame, once it returns `false`
+pub(crate) fn probe_frames<R: Read>(
+    reader: R,
+    options: &ProbeOptions,
+    on_frame: &mut dyn FnMut(&Frame) -> bool,
+) -> Result<GifProbe, ProbeError> {
+    let started = options.timing.then(Instant::now);
+
+    let exceeded = Cell::new(false);
+    let mut reader = SizeLimit {
+        inner: reader,
+        remaining: options.max_file_size.unwrap_or(u64::MAX),
+        exceeded: &exceeded,
+    };
+
+    let mut magic = Vec::with_capacity(12);
+
+    if options.format_hint.is_none() {
+        if let Err(e) = (&mut reader).take(12).read_to_end(&mut magic) {
+            return Err(match exceeded.get() {
+                true => ProbeError::FileTooLarge,
+                false => gif::DecodingError::from(e).into(),
+            });
+        }
+    }
+
+    let format = options.format_hint.or_else(|| ImageFormat::sniff(&magic)).unwrap_or_default();
+
+    // put back what was peeked at
+    let reader = io::Cursor::new(magic).chain(reader);
+
+    let result = match format {
+        ImageFormat::Gif => probe_gif(reader, options, on_frame),
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => probe_buffered(reader, "WebP", options, crate::webp::probe),
+        #[cfg(feature = "apng")]
+        ImageFormat::Png => probe_buffered(reader, "PNG", options, crate::apng::probe),
+        #[cfg(feature = "avif")]
+        ImageFormat::Avif => probe_buffered(reader, "AVIF", options, crate::avif::probe),
+    };
+
+    // whatever the decoder made of the failed read
+    if exceeded.get() {
+        return Err(ProbeError::FileTooLarge);
+    }
+
+    let mut probe = result?;
+
+    probe.format = format;
+
+    if let Some(started) = started {
+        probe.timing.get_or_insert_with(Timing::default).total = started.elapsed();
+    }
+
+    if let Some(ratio) = probe.expansion_ratio().filter(|&ratio| options.max_expansion.is_some_and(|m| m < ratio))
+    {
+        return Err(ProbeError::TooExpansive(ratio));
+    }
+
+    Ok(probe)
+}
+
+/// Probes a format other than GIF with `parse`, which reads its container from the whole input at once, since the
+/// decoders need to seek, which stdin can't. `name` is the format's in errors.
+#[cfg(any(feature = "webp", feature = "apng", feature = "avif"))]
+#[cfg_attr(not(feature = "png"), allow(unused_variables))]
+fn probe_buffered<R: Read>(
+    mut reader: R,
+    name: &str,
+    options: &ProbeOptions,
+    parse: fn(&[u8], &ProbeOptions) -> Result<GifProbe, ProbeError>,
+) -> Result<GifProbe, ProbeError> {
+    #[cfg(feature = "png")]
+    if options.thumbnail.is_some() {
+        return Err(ProbeError::Thumbnail(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("thumbnails of {name} files aren't supported"),
+        )));
+    }
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(gif::DecodingError::from)?;
+
+    let mut probe = parse(&data, options)?;
+
+    probe.bytes_read = data.len() as u64;
+    probe.file_bytes_read = data.len() as u64;
+
+    #[cfg(feature = "hash")]
+    if options.hash {
+        let mut hasher: sha2::Sha256 = sha2::Digest::new();
+        sha2::Digest::update(&mut hasher, &data);
+        probe.sha256 = Some(sha2::Digest::finalize(hasher).into());
+    }
+
+    Ok(probe)
+}
+
+/// Reader adapter that fails once more than `remaining` bytes have been read, without reading much past it.
+struct SizeLimit<'a, R> {
+    inner: R,
+    remaining: u64,
+    /// Set when the limit was hit, to tell that failure apart from the inner reader's
+    exceeded: &'a Cell<bool>,
+}
+
+impl<R: Read> Read for SizeLimit<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // one byte past the limit is enough to know it's exceeded
+        let max = buf.len().min(usize::try_from(self.remaining.saturating_add(1)).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+
+        if n as u64 > self.remaining {
+            self.exceeded.set(true);
+            return Err(io::Error::other("input is larger than the maximum file size"));
+        }
+
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn probe_gif<R: Read>(
+    reader: R,
+    options: &ProbeOptions,
+    on_frame: &mut dyn FnMut(&Frame) -> bool,
+) -> Result<GifProbe, ProbeError> {
+    // each phase of `timing` lasts since the end of the one before
+    let mut phase_start = options.timing.then(Instant::now);
+    let mut phase = || {
+        phase_start.as_mut().map(|start| {
+            let now = Instant::now();
+            now - std::mem::replace(start, now)
+        })
+    };
+
+    let mut reader = Scan::new(reader);
+
+    if options.frame_offsets {
+        reader.scanner.frame_offsets = Some(Vec::new());
+    }
+
+    if options.lzw_stats {
+        reader.scanner.lzw = Some(LzwWidths::default());
+    }
+
+    if options.trailing_data {
+        reader.scanner.trailing = Some(TrailingData::default());
+    }
+
+    if options.comments {
+        reader.scanner.comments = Some(Vec::new());
+    }
+
+    reader.scanner.capture_app_extensions = options.app_extensions;
+    reader.scanner.capture_xmp = options.xmp;
+
+    #[cfg(feature = "hash")]
+    if options.hash {
+        reader.hasher = Some(sha2::Digest::new());
+    }
+
+    let mut opts = DecodeOptions::new();
+
+    opts.set_color_output(ColorOutput::Indexed);
+    // frames overflowing the canvas are reported rather than rejected when validating it
+    opts.check_frame_consistency(!options.validate_canvas);
+    opts.allow_unknown_blocks(false);
+    opts.check_lzw_end_code(false);
+    opts.set_memory_limit(MemoryLimit::Bytes(options.memory_limit()));
+
+    // if in the future `gif` requires `Seek` it'll silently become incompatible with stdin,
+    // so assert that `read_info` can work with only `Read`
+    #[inline(always)]
+    fn assert_read_only<R: Read>(reader: R, opts: DecodeOptions) -> Result<gif::Decoder<R>, gif::DecodingError> {
+        opts.read_info(reader)
+    }
+
+    let mut decoder = assert_read_only(&mut reader, opts)?;
+    let header = phase();
+
+    let mut probe = GifProbe {
+        width: decoder.width(),
+        height: decoder.height(),
+        format: ImageFormat::Gif,
+        codec: Codec::Lzw,
+        version: Version::default(),
+        background_index: 0,
+        background_color: None,
+        pixel_aspect_ratio: None,
+        alpha: false,
+        alpha_coverage: None,
+        max_colors: 0,
+        duration: 0,
+        duration_normalized: 0,
+        frames: 0,
+        min_delay: None,
+        max_delay: None,
+        first_frame_full: false,
+        first_frame_standalone: false,
+        first_frame_palette_source: PaletteSource::None,
+        first_frame_dispose: None,
+        first_frame_transparent_index: None,
+        any_later_transparent_index: false,
+        safe_first_frame_preview: false,
+        frameless: false,
+        has_color_profile: false,
+        loop_count: None,
+        has_xmp: false,
+        warnings: Vec::new(),
+        fps_timeline: options.fps_timeline.then(Vec::new),
+        canvas: options.validate_canvas.then(CanvasCheck::default),
+        first_frame_bit_depth: None,
+        zero_delay_frames: options.frame_stats.then_some(0),
+        longest_frame: None,
+        dominant_color: None,
+        average_color: options.average_color.then_some(None),
+        dominant_colors: options.dominant_colors.map(|_| Vec::new()),
+        blurhash: None,
+        phash: None,
+        palette_stats: None,
+        used_colors: None,
+        max_simultaneous_colors: None,
+        grayscale: None,
+        max_lzw_code_width: None,
+        alpha_explanation: None,
+        rendered_alpha: None,
+        bytes_read: 0,
+        file_bytes_read: 0,
+        memory_peak: 0,
+        #[cfg(feature = "hash")]
+        sha256: None,
+        trailing_bytes: None,
+        polyglot_suspect: None,
+        frame_offsets: None,
+        frames_detail: options.detail_frames.map(|_| Vec::new()),
+        frame_at: options.frame_at.map(|_| None),
+        truncated: (options.max_frames.is_some() || options.max_duration.is_some()).then_some(false),
+        app_extensions: None,
+        comments: None,
+        xmp: None,
+        classification: None,
+        ui_detection: None,
+        static_suggestion: None,
+        timing: None,
+    };
+
+    let mut analyses = Analyses::new(options, probe.width, probe.height);
+
+    if matches!(options.max_pixels, Some(m) if m < (probe.width as u64 * probe.height as u64)) {
+        return Err(ProbeError::TooLarge);
+    }
+
+    if let Some(p) = decoder.global_palette() {
+        probe.max_colors = colors(p);
+    }
+
+    let global_palette = decoder.global_palette().map(<[u8]>::to_vec);
+
+    #[cfg(feature = "png")]
+    let mut thumbnail = (options.thumbnail.as_deref())
+        .map(|path| Thumbnail::new(path, options.thumbnail_at, probe.width, probe.height));
+
+    let first = decoder.read_next_frame()?;
+    // stopping before the first frame leaves them all uncounted
+    let stopped = first.is_some_and(|frame| !on_frame(frame));
+
+    if stopped {
+        probe.truncated = Some(true);
+    } else if let Some(frame) = first {
+        #[cfg(feature = "png")]
+        if let Some(ref mut thumbnail) = thumbnail {
+            let delay = browser_delay(frame.delay) as u64 * 10;
+            thumbnail.add_frame(frame, global_palette.as_deref(), delay).map_err(ProbeError::Thumbnail)?;
+        }
+
+        let transparent_count =
+            frame.transparent.map_or(0, |tr| frame.buffer.iter().filter(|&&i| i == tr).count());
+        let transparent_pixels = transparent_count > 0;
+
+        if transparent_pixels && options.explain_alpha {
+            probe.alpha_explanation = Some(AlphaExplanation::FirstFramePixels {
+                transparent_pixels: transparent_count as u64,
+            });
+        }
+
+        probe.alpha_coverage =
+            (!frame.buffer.is_empty()).then(|| transparent_count as f64 / frame.buffer.len() as f64);
+
+        probe.alpha |= transparent_pixels;
+        probe.first_frame_full = covers(frame, probe.width, probe.height);
+        probe.first_frame_standalone = probe.first_frame_full && !transparent_pixels;
+        probe.first_frame_palette_source = match frame.palette {
+            Some(_) => PaletteSource::Local,
+            None if global_palette.is_some() => PaletteSource::Global,
+            None => PaletteSource::None,
+        };
+
+        probe.first_frame_dispose = Some(frame.dispose);
+        probe.first_frame_transparent_index = frame.transparent;
+        // later frames build on the first one unless it's cleared away
+        probe.safe_first_frame_preview =
+            probe.first_frame_standalone && matches!(frame.dispose, DisposalMethod::Any | DisposalMethod::Keep);
+
+        if options.frame_stats {
+            probe.first_frame_bit_depth = frame.palette.as_deref().or(global_palette.as_deref()).map(bit_depth);
+        }
+
+        if options.dominant_color {
+            probe.dominant_color = dominant_color(frame, global_palette.as_deref());
+        }
+
+        if options.average_color {
+            probe.average_color = Some(average_color(frame, global_palette.as_deref()));
+        }
+
+        if let Some(limit) = options.dominant_colors {
+            probe.dominant_colors = Some(dominant_colors(frame, global_palette.as_deref(), limit));
+        }
+
+        if options.blurhash {
+            probe.blurhash = crate::blurhash::encode(probe.width, probe.height, frame, global_palette.as_deref());
+        }
+
+        if options.phash {
+            probe.phash = crate::phash::dhash(probe.width, probe.height, frame, global_palette.as_deref());
+        }
+
+        if options.frame_at == Some(probe.frames) {
+            probe.frame_at = Some(Some(FrameAt::new(probe.frames, frame, global_palette.as_deref())));
+        }
+
+        probe.add_frame(frame, options);
+        analyses.add_frame(frame, global_palette.as_deref());
+    } else {
+        probe.frameless = true;
+    }
+
+    let first_frame = phase();
+    let max_duration = options.max_duration.unwrap_or(u64::MAX);
+
+    // whether the decoder walked to the end of the stream, rather than stopping early
+    let mut complete = true;
+
+    // index and area of the last frame disposed to the background, until the next frame shows
+    // whether it paints over all of it
+    let mut cleared: Option<(u64, Area)> = None;
+
+    loop {
+        options.check_cancelled()?;
+
+        // nothing past the requested frame is needed, or past where `on_frame` stopped
+        if stopped || matches!(options.frame_at, Some(n) if probe.frames > n) {
+            complete = false;
+            break;
+        }
+
+        if matches!(options.max_frames, Some(max) if probe.frames >= max.get()) || probe.duration >= max_duration {
+            // only the header of the next frame, if any, to tell whether there were more
+            let more = !matches!(decoder.next_frame_info(), Ok(None));
+
+            complete = !more;
+            probe.truncated = Some(more);
+            break;
+        }
+
+        // frames before the thumbnail's are composited under it
+        #[cfg(feature = "png")]
+        let thumbnail_pending = thumbnail.as_ref().is_some_and(Thumbnail::pending);
+        #[cfg(not(feature = "png"))]
+        let thumbnail_pending = false;
+
+        // only the requested frame needs decoding for its transparent pixels
+        let frame = match analyses.decode_all() || thumbnail_pending || options.frame_at == Some(probe.frames) {
+            true => decoder.read_next_frame()?,
+            false => decoder.next_frame_info()?,
+        };
+
+        let Some(frame) = frame else { break };
+
+        if !on_frame(frame) {
+            complete = false;
+            probe.truncated = Some(true);
+            break;
+        }
+
+        probe.any_later_transparent_index |= frame.transparent.is_some();
+
+        if let Some((index, area)) = cleared.take() {
+            if !repaints(frame, area) {
+                background_alpha(&mut probe, index, options);
+            }
+        }
+
+        if !probe.alpha && frame.dispose == DisposalMethod::Background && frame.width > 0 && frame.height > 0 {
+            cleared = Some((probe.frames, Area::of(frame)));
+        }
+
+        if options.frame_at == Some(probe.frames) {
+            probe.frame_at = Some(Some(FrameAt::new(probe.frames, frame, global_palette.as_deref())));
+        }
+
+        #[cfg(feature = "png")]
+        if let Some(ref mut thumbnail) = thumbnail {
+            let delay = browser_delay(frame.delay) as u64 * 10;
+            thumbnail.add_frame(frame, global_palette.as_deref(), delay).map_err(ProbeError::Thumbnail)?;
+        }
+
+        probe.add_frame(frame, options);
+        analyses.add_frame(frame, global_palette.as_deref());
+    }
+
+    let remaining_frames = phase();
+
+    if options.timing {
+        // the total is filled in once everything else is done
+        probe.timing = Some(Timing {
+            total: Duration::ZERO,
+            header,
+            first_frame,
+            remaining_frames,
+        });
+    }
+
+    // nothing seen paints over the last cleared area, or walking stopped before what might have
+    if let Some((index, _)) = cleared {
+        background_alpha(&mut probe, index, options);
+    }
+
+    drop(decoder);
+
+    #[cfg(feature = "png")]
+    if let Some(thumbnail) = thumbnail {
+        thumbnail.finish().map_err(ProbeError::Thumbnail)?;
+    }
+
+    if options.explain_alpha && !probe.alpha {
+        probe.alpha_explanation = Some(AlphaExplanation::None);
+    }
+
+    // hashing and looking past the trailer need the whole input too
+    #[cfg(feature = "hash")]
+    let drain = options.drain || options.hash || options.trailing_data;
+    #[cfg(not(feature = "hash"))]
+    let drain = options.drain || options.trailing_data;
+
+    if drain {
+        io::copy(&mut reader, &mut io::sink()).map_err(gif::DecodingError::from)?;
+    }
+
+    probe.bytes_read = reader.scanner.bytes_read();
+    // the decoder rejects any other version
+    probe.version = match &reader.scanner.version {
+        b"87a" => Version::Gif87a,
+        _ => Version::Gif89a,
+    };
+
+    probe.background_index = reader.scanner.background;
+    probe.background_color = global_palette
+        .as_deref()
+        .and_then(|p| p.chunks_exact(3).nth(probe.background_index as usize))
+        .map(|rgb| [rgb[0], rgb[1], rgb[2]]);
+
+    // the byte stores the ratio in 1/64ths, offset by 15 so it covers 1:4 up to 4:1
+    probe.pixel_aspect_ratio = match reader.scanner.aspect_ratio {
+        0 => None,
+        n => Some((n as f64 + 15.0) / 64.0),
+    };
+    probe.has_color_profile = reader.scanner.has_color_profile;
+    probe.loop_count = reader.scanner.loop_count();
+
+    probe.has_xmp = reader.scanner.has_xmp;
+
+    if options.xmp {
+        probe.xmp = Some(reader.scanner.xmp().map(|packet| String::from_utf8_lossy(packet).into_owned()));
+    }
+
+    // with frames left, only the walked frames are checked, up to the start of the last one's image data, since
+    // how far past that the decoder read ahead depends on its buffer rather than the file
+    let checked_until = match complete {
+        true => None,
+        false => Some(
+            (probe.frames.checked_sub(1))
+                .and_then(|last| reader.scanner.image_data.get(last as usize))
+                .map_or(0, |&(start, _)| start + 1),
+        ),
+    };
+
+    if reader
+        .scanner
+        .violations_before(checked_until)
+        .any(|v| matches!(v, Violation::VersionBlockConflict { .. }))
+    {
+        probe.warnings.push("version_block_conflict");
+    }
+
+    #[cfg(feature = "hash")]
+    {
+        probe.sha256 = reader.hasher.take().map(|hasher| sha2::Digest::finalize(hasher).into());
+    }
+
+    analyses.finish(&mut probe, options);
+
+    if let Some(ref rules) = options.detect_ui {
+        probe.ui_detection = Some(UiDetection::new(&probe, probe.loop_count, rules));
+    }
+
+    if options.strict {
+        reader.scanner.check(complete, checked_until).map_err(ProbeError::Strict)?;
+
+        if probe.frameless {
+            return Err(ProbeError::Strict(Violation::Frameless));
+        }
+    }
+
+    if let Some(mut offsets) = reader.scanner.frame_offsets.take() {
+        // the decoder reads ahead, so the scanner may have seen frames we didn't walk
+        offsets.truncate(probe.frames as usize);
+        probe.frame_offsets = Some(offsets);
+    }
+
+    // the decoder reads ahead, so this is only past the frames we walked
+    let image_data = &reader.scanner.image_data;
+    let compressed_size = |index: u64| match image_data.get(index as usize) {
+        Some(&(start, Some(end))) => Some(end - start),
+        _ => None,
+    };
+
+    if let Some(ref mut detail) = probe.frames_detail {
+        for (index, frame) in detail.iter_mut().enumerate() {
+            frame.compressed_size = compressed_size(index as u64);
+        }
+    }
+
+    if let Some(Some(ref mut frame_at)) = probe.frame_at {
+        frame_at.detail.compressed_size = compressed_size(frame_at.index);
+    }
+
+    let last_frame_end = probe.frames.checked_sub(1).and_then(|last| image_data.get(last as usize)?.1);
+
+    probe.file_bytes_read = match (complete, reader.scanner.trailer, last_frame_end) {
+        (true, Some(end), _) | (false, _, Some(end)) => end,
+        _ => probe.bytes_read,
+    };
+
+    if let Some(trailing) = reader.scanner.trailing.take() {
+        probe.trailing_bytes = Some(trailing.bytes);
+        probe.polyglot_suspect = Some(trailing.suspect);
+    }
+
+    if let Some(mut lzw) = reader.scanner.lzw.take() {
+        // same as for the offsets, ignore frames read ahead
+        lzw.frames.truncate(probe.frames as usize);
+        probe.max_lzw_code_width = lzw.frames.into_iter().max().filter(|&width| width > 0);
+    }
+
+    if options.app_extensions {
+        probe.app_extensions = Some(std::mem::take(&mut reader.scanner.app_extensions));
+    }
+
+    probe.comments = reader.scanner.comments.take();
+
+    Ok(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use super::*;
+
+    const FRAMELESS: &[u8] = include_bytes!("../tests/fixtures/frameless.gif");
+    const GCE_87A: &[u8] = include_bytes!("../tests/fixtures/gce_87a.gif");
+    const GCE_89A: &[u8] = include_bytes!("../tests/fixtures/gce_89a.gif");
+    const LATE_VIOLATION: &[u8] = include_bytes!("../tests/fixtures/late_violation.gif");
+
+    fn strict() -> ProbeOptions {
+        ProbeOptions {
+            strict: true,
+            ..ProbeOptions::default()
+        }
+    }
+
+    #[test]
+    fn frameless() {
+        // only a header, a logical screen descriptor and the trailer
+        let probe = probe(FRAMELESS, &ProbeOptions::default()).unwrap();
+
+        assert!(probe.frameless);
+        assert_eq!(probe.frames, 0);
+        assert!(!probe.alpha);
+    }
+
+    #[test]
+    fn strict_rejects_frameless() {
+        assert!(matches!(
+            probe(FRAMELESS, &strict()),
+            Err(ProbeError::Strict(Violation::Frameless))
+        ));
+    }
+
+    #[test]
+    fn version_block_conflict() {
+        let conflict = |probe: &GifProbe| probe.warnings.contains(&"version_block_conflict");
+
+        let probe_87a = probe(GCE_87A, &ProbeOptions::default()).unwrap();
+        assert_eq!(probe_87a.version, Version::Gif87a);
+        assert!(conflict(&probe_87a));
+
+        let probe_89a = probe(GCE_89A, &ProbeOptions::default()).unwrap();
+        assert_eq!(probe_89a.version, Version::Gif89a);
+        assert!(!conflict(&probe_89a));
+    }
+
+    #[test]
+    fn strict_rejects_version_block_conflict() {
+        // the graphic control extension follows the header and 4-color global palette
+        assert!(matches!(
+            probe(GCE_87A, &strict()),
+            Err(ProbeError::Strict(Violation::VersionBlockConflict { offset: 25 }))
+        ));
+
+        assert!(probe(GCE_89A, &strict()).is_ok());
+    }
+
+    #[test]
+    fn strict_checks_the_whole_stream() {
+        let result = probe(LATE_VIOLATION, &strict());
+
+        assert!(matches!(
+            result,
+            Err(ProbeError::Strict(Violation::ExtensionBlockSize {
+                label: 0xFF,
+                size: 10,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn strict_ignores_what_was_read_ahead() {
+        let options = ProbeOptions {
+            max_frames: NonZeroU64::new(1),
+            ..strict()
+        };
+
+        // the whole file fits in the decoder's buffer, so the scanner sees the second frame either way
+        let probe = probe(LATE_VIOLATION, &options).unwrap();
+
+        assert_eq!(probe.frames, 1);
+        assert_eq!(probe.truncated, Some(true));
+    }
+
+    #[test]
+    fn strict_reports_a_missing_terminator() {
+        // cut off inside the second frame's image data
+        let mut scanner = crate::scan::Scanner::default();
+        scanner.feed(&LATE_VIOLATION[..LATE_VIOLATION.len() - 4]);
+        scanner.end();
+
+        assert!(scanner
+            .violations_before(None)
+            .any(|v| matches!(v, Violation::MissingTerminator { offset: 0x47 })));
+    }
+}
@@ -0,0 +1,71 @@
+//! Walking a GIF's frames one at a time, for callers with their own rules for when to stop.
+
+use std::{
+    io::Read,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+
+use crate::{probe::probe_frames, FrameDetail, GifProbe, ProbeError, ProbeOptions};
+
+/// Yields the [`FrameDetail`] of each frame as the probe reaches it, then the summary from
+/// [`finish`](Self::finish).
+///
+/// The probe runs on its own thread, waiting for each frame to be taken before walking on to the next, so
+/// frames that aren't asked for aren't read. Inputs other than GIFs yield no frames, but still have a summary.
+/// [`FrameDetail::compressed_size`] is always `None`, since a frame's data is only read after it's yielded.
+///
+/// ```no_run
+/// use gif_probe::{ProbeIter, ProbeOptions};
+///
+/// let file = std::io::BufReader::new(std::fs::File::open("file.gif").unwrap());
+/// let mut frames = ProbeIter::new(file, ProbeOptions::default());
+///
+/// // stop at the first frame that's shown for more than a second
+/// for frame in &mut frames {
+///     if frame.delay > 100 {
+///         break;
+///     }
+/// }
+///
+/// let probe = frames.finish().unwrap();
+/// ```
+pub struct ProbeIter {
+    frames: mpsc::Receiver<FrameDetail>,
+    probing: JoinHandle<Result<GifProbe, ProbeError>>,
+}
+
+impl ProbeIter {
+    /// Starts probing `reader` with `options`, up to the first frame
+    pub fn new<R: Read + Send + 'static>(reader: R, options: ProbeOptions) -> Self {
+        // frames are handed over one at a time, so the probe is never more than one ahead
+        let (tx, frames) = mpsc::sync_channel(0);
+
+        let probing = thread::spawn(move || {
+            // the iterator hung up, so it's done with the frames
+            probe_frames(reader, &options, &mut |frame| tx.send(FrameDetail::new(frame)).is_ok())
+        });
+
+        ProbeIter { frames, probing }
+    }
+
+    /// Stops probing, and returns the summary of the frames yielded so far, or of every frame if they all were.
+    ///
+    /// If frames were left, they're not counted and [`GifProbe::truncated`] is `Some(true)`, and the input isn't
+    /// read any further unless something like [`ProbeOptions::drain`] asks for it. Fails like
+    /// [`probe`](crate::probe) would, which is also why iterating ended early, if it did.
+    pub fn finish(self) -> Result<GifProbe, ProbeError> {
+        drop(self.frames);
+
+        self.probing.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+}
+
+impl Iterator for ProbeIter {
+    type Item = FrameDetail;
+
+    /// The next frame, or `None` once there are no more or probing failed, which [`finish`](Self::finish) tells
+    fn next(&mut self) -> Option<FrameDetail> {
+        self.frames.recv().ok()
+    }
+}
@@ -0,0 +1,44 @@
+//! Python module for the `python` feature, so pipelines can probe in-process instead of running the binary for
+//! every file.
+
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{GifProbe, JsonOptions, ProbeError, ProbeOptions};
+
+/// Probes the bytes of a whole file like the binary does without flags, returning the record as a dict.
+/// Raises `ValueError` if probing fails.
+#[pyfunction]
+fn probe(py: Python<'_>, data: &[u8]) -> PyResult<PyObject> {
+    // other Python threads can run while probing
+    let result = py.allow_threads(|| crate::probe(data, &ProbeOptions::default()));
+
+    to_dict(py, result)
+}
+
+/// Same as `probe`, for the file at `path`. Raises `OSError` if it can't be opened.
+#[pyfunction]
+fn probe_file(py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+    let file = File::open(path)?;
+    let result = py.allow_threads(|| crate::probe(BufReader::new(file), &ProbeOptions::default()));
+
+    to_dict(py, result)
+}
+
+/// The JSON output loaded by Python's `json` module, so the dict has the same keys and values
+fn to_dict(py: Python<'_>, result: Result<GifProbe, ProbeError>) -> PyResult<PyObject> {
+    let probe = result.map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let json = probe.to_json(&JsonOptions::default());
+
+    Ok(py.import_bound("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+#[pymodule]
+#[pyo3(name = "gif_probe")]
+fn module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(probe, m)?)?;
+    m.add_function(wrap_pyfunction!(probe_file, m)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,817 @@
+//! Follows the raw GIF block structure as the decoder reads it.
+//!
+//! The `gif` crate only surfaces decoded frames, so anything about the container itself
+//! (sub-block framing, extension sizes, the trailer) is tracked here with a small state machine
+//! fed by [`Scan`], which tees every byte read from the input.
+
+use std::io::{self, Read};
+
+use serde::Serialize;
+
+/// Reader adapter that feeds everything read through it into a [`Scanner`].
+pub struct Scan<R> {
+    inner: R,
+    pub scanner: Scanner,
+
+    /// Digest of everything read, when requested
+    #[cfg(feature = "hash")]
+    pub hasher: Option<sha2::Sha256>,
+}
+
+impl<R> Scan<R> {
+    pub fn new(inner: R) -> Self {
+        Scan {
+            inner,
+            scanner: Scanner::default(),
+            #[cfg(feature = "hash")]
+            hasher: None,
+        }
+    }
+}
+
+impl<R: Read> Read for Scan<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.scanner.feed(&buf[..n]);
+
+        if n == 0 && !buf.is_empty() {
+            self.scanner.end();
+        }
+
+        #[cfg(feature = "hash")]
+        if let Some(ref mut hasher) = self.hasher {
+            sha2::Digest::update(hasher, &buf[..n]);
+        }
+
+        Ok(n)
+    }
+}
+
+/// Structural problems the decoder tolerates, but a strict reading of the spec doesn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// A byte at a block boundary isn't an extension, image or trailer introducer,
+    /// usually because the previous block is missing its `0x00` terminator.
+    InvalidIntroducer { offset: u64, byte: u8 },
+
+    /// A graphic control, application or plain text extension whose first sub-block isn't the size
+    /// fixed by the spec, or a graphic control extension with more than one sub-block.
+    ExtensionBlockSize { offset: u64, label: u8, size: u8 },
+
+    /// LZW minimum code size outside of `2..=8`.
+    LzwCodeSize { offset: u64, size: u8 },
+
+    /// The stream ended inside the block starting at `offset`, before its `0x00` terminator.
+    MissingTerminator { offset: u64 },
+
+    /// The stream ended without a trailer.
+    MissingTrailer,
+
+    /// The stream has no image blocks, so it renders nothing.
+    Frameless,
+
+    /// A graphic control extension in a `GIF87a` stream, which predates them.
+    VersionBlockConflict { offset: u64 },
+}
+
+impl Violation {
+    /// Stream offset the violation was found at, `None` for those about the stream as a whole
+    pub fn offset(&self) -> Option<u64> {
+        match *self {
+            Violation::InvalidIntroducer { offset, .. }
+            | Violation::ExtensionBlockSize { offset, .. }
+            | Violation::LzwCodeSize { offset, .. }
+            | Violation::MissingTerminator { offset }
+            | Violation::VersionBlockConflict { offset } => Some(offset),
+            Violation::MissingTrailer | Violation::Frameless => None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+enum State {
+    /// Collecting the header and logical screen descriptor
+    #[default]
+    Header,
+    GlobalPalette(usize),
+    Introducer,
+    Label,
+    ImageDescriptor,
+    LocalPalette(usize),
+    LzwCodeSize,
+    SubBlockSize,
+    SubBlockData(usize),
+    Trailer,
+    /// Lost track of the structure, stop following along
+    Broken,
+}
+
+#[derive(Default, Clone, Copy)]
+enum Block {
+    #[default]
+    Image,
+    Extension(u8),
+}
+
+const EXT_PLAIN_TEXT: u8 = 0x01;
+const EXT_GRAPHIC_CONTROL: u8 = 0xF9;
+const EXT_COMMENT: u8 = 0xFE;
+const EXT_APPLICATION: u8 = 0xFF;
+
+/// Application extension, with its data sub-blocks left as they appear in the stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppExtension {
+    /// Stream offset of the extension introducer
+    pub offset: u64,
+    #[serde(serialize_with = "crate::output::lossy")]
+    pub identifier: [u8; 8],
+    #[serde(serialize_with = "crate::output::lossy")]
+    pub auth_code: [u8; 3],
+    /// Data sub-blocks after the identifier, including their size bytes but not the terminator
+    #[serde(rename = "payload", serialize_with = "crate::output::payload")]
+    pub data: Vec<u8>,
+    /// Whether `data` was cut off at [`MAX_CAPTURE`] bytes, or once [`MAX_CAPTURE_TOTAL`] were kept
+    pub truncated: bool,
+}
+
+impl AppExtension {
+    /// Contents of the data sub-blocks joined together, without their size bytes
+    pub fn payload(&self) -> Vec<u8> {
+        join_sub_blocks(&self.data)
+    }
+}
+
+/// Comment extension, decoded as UTF-8 with invalid sequences replaced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Comment {
+    /// Stream offset of the extension introducer
+    pub offset: u64,
+    pub text: String,
+    /// Whether `text` was cut off at [`MAX_COMMENT`] bytes, or once [`MAX_CAPTURE_TOTAL`] were kept
+    pub truncated: bool,
+}
+
+/// Most bytes of a single extension kept in memory
+pub const MAX_CAPTURE: usize = 1 << 20;
+
+/// Most bytes of all extensions together kept in memory, however many there are
+pub const MAX_CAPTURE_TOTAL: usize = 4 << 20;
+
+/// Application extensions with a loop count, by identifier and auth code, which are always kept
+const LOOP_EXTENSIONS: [(&[u8; 8], &[u8; 3]); 2] = [(b"NETSCAPE", b"2.0"), (b"ANIMEXTS", b"1.0")];
+
+const XMP_EXTENSION: (&[u8; 8], &[u8; 3]) = (b"XMP Data", b"XMP");
+const ICC_EXTENSION: (&[u8; 8], &[u8; 3]) = (b"ICCRGBG1", b"012");
+
+/// Most bytes of a single comment kept, they're meant to be short and end up in the output
+pub const MAX_COMMENT: usize = 64 << 10;
+
+#[derive(Default)]
+pub struct Scanner {
+    pos: u64,
+    state: State,
+    block: Block,
+    /// Stream offset of the current block's introducer
+    block_start: u64,
+    sub_blocks: usize,
+
+    /// fixed-size header fields, only the first `len` bytes are valid
+    fixed: [u8; 13],
+    len: usize,
+
+    pub violations: Vec<Violation>,
+
+    /// Stream offset just past the trailer, once it's seen
+    pub trailer: Option<u64>,
+
+    /// Version from the header, e.g. `89a`
+    pub version: [u8; 3],
+
+    /// Background color index from the logical screen descriptor
+    pub background: u8,
+
+    /// Pixel aspect ratio byte from the logical screen descriptor, 0 when unspecified
+    pub aspect_ratio: u8,
+
+    /// Stream offset of each image descriptor, when requested
+    pub frame_offsets: Option<Vec<u64>>,
+
+    /// Stream offsets of where each frame's image data starts, at its LZW minimum code size, and of just past
+    /// its block terminator, `None` until that's seen
+    pub image_data: Vec<(u64, Option<u64>)>,
+
+    /// Code widths reached by each frame's LZW data, when requested
+    pub lzw: Option<LzwWidths>,
+
+    /// Sub-blocks of the extension being read, if it's one we keep
+    capture: Option<(u64, Vec<u8>, bool)>,
+    /// Bytes of extensions kept so far, up to [`MAX_CAPTURE_TOTAL`]
+    captured: usize,
+
+    /// Keep every application extension, not only those the summary needs
+    pub capture_app_extensions: bool,
+    /// Keep the XMP packet, otherwise only [`has_xmp`](Self::has_xmp) is set
+    pub capture_xmp: bool,
+    pub app_extensions: Vec<AppExtension>,
+
+    /// Whether an `XMP Data` application extension with auth code `XMP` was seen, which carries an XMP packet
+    pub has_xmp: bool,
+
+    /// Whether an `ICCRGBG1` application extension with auth code `012` was seen, which embeds an ICC profile
+    pub has_color_profile: bool,
+
+    /// Comment extensions, when requested
+    pub comments: Option<Vec<Comment>>,
+
+    /// What comes after the trailer, when requested
+    pub trailing: Option<TrailingData>,
+}
+
+impl Scanner {
+    /// The first violation found before `until`, or anywhere if it's `None`, treating a missing trailer as one
+    /// if the decoder walked the whole stream.
+    pub fn check(&self, complete: bool, until: Option<u64>) -> Result<(), Violation> {
+        match self.violations_before(until).next() {
+            Some(v) => Err(v),
+            None if complete && self.trailer.is_none() => Err(Violation::MissingTrailer),
+            None => Ok(()),
+        }
+    }
+
+    /// Violations found before `until`, or anywhere if it's `None`. The decoder reads ahead of what it's asked
+    /// for, so when probing stops early, only what comes before where it stopped is the same for any input.
+    pub fn violations_before(&self, until: Option<u64>) -> impl Iterator<Item = Violation> + '_ {
+        self.violations.iter().copied().filter(move |v| match until {
+            Some(until) => v.offset().is_some_and(|offset| offset < until),
+            None => true,
+        })
+    }
+
+    /// Called once the input ends, to tell a block cut off before its terminator apart from a missing trailer
+    pub fn end(&mut self) {
+        if let State::Label
+        | State::ImageDescriptor
+        | State::LocalPalette(_)
+        | State::LzwCodeSize
+        | State::SubBlockSize
+        | State::SubBlockData(_) = self.state
+        {
+            self.violations.push(Violation::MissingTerminator {
+                offset: self.block_start,
+            });
+            self.state = State::Broken;
+        }
+    }
+
+    /// Number of bytes fed so far
+    pub fn bytes_read(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn feed(&mut self, mut buf: &[u8]) {
+        while let Some((&byte, rest)) = buf.split_first() {
+            match self.state {
+                // bulk-skip uninteresting payloads
+                State::GlobalPalette(n) | State::LocalPalette(n) | State::SubBlockData(n) => {
+                    let take = n.min(buf.len());
+
+                    if let (State::SubBlockData(_), Some(capture)) = (self.state, &mut self.capture) {
+                        push_capture(capture, &mut self.captured, &buf[..take]);
+                    }
+
+                    if let (State::SubBlockData(_), Block::Image, Some(lzw)) =
+                        (self.state, self.block, &mut self.lzw)
+                    {
+                        lzw.feed(&buf[..take]);
+                    }
+
+                    self.pos += take as u64;
+                    buf = &buf[take..];
+
+                    let left = n - take;
+                    self.state = match self.state {
+                        State::GlobalPalette(_) if left > 0 => State::GlobalPalette(left),
+                        State::LocalPalette(_) if left > 0 => State::LocalPalette(left),
+                        State::SubBlockData(_) if left > 0 => State::SubBlockData(left),
+                        State::GlobalPalette(_) => State::Introducer,
+                        State::LocalPalette(_) => State::LzwCodeSize,
+                        _ => State::SubBlockSize,
+                    };
+
+                    // the identifier and auth code are the whole first sub-block
+                    if let (State::SubBlockSize, Block::Extension(EXT_APPLICATION), 1) =
+                        (self.state, self.block, self.sub_blocks)
+                    {
+                        self.identify_application();
+                    }
+
+                    continue;
+                }
+                State::Trailer | State::Broken => {
+                    if let (State::Trailer, Some(trailing)) = (self.state, &mut self.trailing) {
+                        trailing.feed(buf);
+                    }
+
+                    self.pos += buf.len() as u64;
+                    return;
+                }
+                _ => self.byte(byte),
+            }
+
+            self.pos += 1;
+            buf = rest;
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        self.state = match self.state {
+            State::Header | State::ImageDescriptor => {
+                self.fixed[self.len] = byte;
+                self.len += 1;
+                self.fixed_field()
+            }
+            State::Introducer => match byte {
+                0x21 => {
+                    self.block_start = self.pos;
+                    State::Label
+                }
+                0x2C => {
+                    self.block_start = self.pos;
+
+                    if let Some(ref mut offsets) = self.frame_offsets {
+                        offsets.push(self.pos);
+                    }
+
+                    self.len = 0;
+                    State::ImageDescriptor
+                }
+                0x3B => {
+                    self.trailer = Some(self.pos + 1);
+                    State::Trailer
+                }
+                _ => {
+                    self.violations.push(Violation::InvalidIntroducer { offset: self.pos, byte });
+                    State::Broken
+                }
+            },
+            State::Label => {
+                self.block = Block::Extension(byte);
+                self.sub_blocks = 0;
+
+                if byte == EXT_GRAPHIC_CONTROL && self.version == *b"87a" {
+                    // offset of the introducer, one byte back
+                    self.violations.push(Violation::VersionBlockConflict { offset: self.pos - 1 });
+                }
+
+                if byte == EXT_APPLICATION || (byte == EXT_COMMENT && self.comments.is_some()) {
+                    // offset of the introducer, one byte back
+                    self.capture = Some((self.pos - 1, Vec::new(), false));
+                }
+
+                State::SubBlockSize
+            }
+            State::LzwCodeSize => {
+                if !(2..=8).contains(&byte) {
+                    self.violations.push(Violation::LzwCodeSize {
+                        offset: self.pos,
+                        size: byte,
+                    });
+                }
+
+                if let Some(ref mut lzw) = self.lzw {
+                    lzw.start(byte);
+                }
+
+                self.image_data.push((self.pos, None));
+
+                self.block = Block::Image;
+                self.sub_blocks = 0;
+                State::SubBlockSize
+            }
+            State::SubBlockSize if byte == 0 => {
+                if let (Block::Image, Some((_, end))) = (self.block, self.image_data.last_mut()) {
+                    *end = Some(self.pos + 1);
+                }
+
+                if let Some((offset, data, truncated)) = self.capture.take() {
+                    match self.block {
+                        Block::Extension(EXT_COMMENT) => self.finish_comment(offset, data, truncated),
+                        _ => self.finish_extension(offset, data, truncated),
+                    }
+                }
+
+                State::Introducer
+            }
+            State::SubBlockSize => {
+                if let Some(ref mut capture) = self.capture {
+                    push_capture(capture, &mut self.captured, &[byte]);
+                }
+
+                if let Block::Extension(label) = self.block {
+                    let expected = match label {
+                        EXT_GRAPHIC_CONTROL => Some(4),
+                        EXT_APPLICATION => Some(11),
+                        EXT_PLAIN_TEXT => Some(12),
+                        _ => None,
+                    };
+
+                    let bad_size = self.sub_blocks == 0 && matches!(expected, Some(e) if e != byte);
+                    let extra = self.sub_blocks > 0 && label == EXT_GRAPHIC_CONTROL;
+
+                    if bad_size || extra {
+                        self.violations.push(Violation::ExtensionBlockSize {
+                            offset: self.pos,
+                            label,
+                            size: byte,
+                        });
+                    }
+                }
+
+                self.sub_blocks += 1;
+                State::SubBlockData(byte as usize)
+            }
+            state => state,
+        };
+    }
+
+    /// Stops keeping an application extension once its identifier shows nothing needs it
+    fn identify_application(&mut self) {
+        let Some((_, ref data, _)) = self.capture else {
+            return;
+        };
+
+        let (looping, xmp, icc) = match data[..] {
+            [11, ..] if data.len() >= 12 => {
+                let id = (&data[1..9], &data[9..12]);
+                let is = |(identifier, auth_code): (&[u8; 8], &[u8; 3])| id == (&identifier[..], &auth_code[..]);
+
+                (
+                    LOOP_EXTENSIONS.into_iter().any(is),
+                    is(XMP_EXTENSION),
+                    is(ICC_EXTENSION),
+                )
+            }
+            // malformed, and only reported by `--strict`
+            _ => (false, false, false),
+        };
+
+        self.has_xmp |= xmp;
+        self.has_color_profile |= icc;
+
+        if !(self.capture_app_extensions || looping || xmp && self.capture_xmp) {
+            self.captured -= data.len();
+            self.capture = None;
+        }
+    }
+
+    fn finish_extension(&mut self, offset: u64, data: Vec<u8>, truncated: bool) {
+        // application extensions start with an 11-byte sub-block of identifier and auth code,
+        // anything else is malformed and only reported by `--strict`
+        if data.len() < 12 || data[0] != 11 {
+            self.captured -= data.len();
+            return;
+        }
+
+        // the first sub-block isn't part of what's kept
+        self.captured -= 12;
+
+        let mut ext = AppExtension {
+            offset,
+            identifier: [0; 8],
+            auth_code: [0; 3],
+            data: data[12..].to_vec(),
+            truncated,
+        };
+
+        ext.identifier.copy_from_slice(&data[1..9]);
+        ext.auth_code.copy_from_slice(&data[9..12]);
+
+        self.app_extensions.push(ext);
+    }
+
+    fn finish_comment(&mut self, offset: u64, data: Vec<u8>, truncated: bool) {
+        let mut text = join_sub_blocks(&data);
+        let truncated = truncated || text.len() > MAX_COMMENT;
+        text.truncate(MAX_COMMENT);
+
+        self.captured -= data.len() - text.len();
+
+        if let Some(ref mut comments) = self.comments {
+            comments.push(Comment {
+                offset,
+                text: String::from_utf8_lossy(&text).into_owned(),
+                truncated,
+            });
+        }
+    }
+
+    /// Loop count from a `NETSCAPE2.0` or `ANIMEXTS1.0` application extension, where 0 means forever.
+    /// GIFs without one play once.
+    pub fn loop_count(&self) -> Option<u16> {
+        self.app_extensions.iter().find_map(|ext| {
+            let known = LOOP_EXTENSIONS.contains(&(&ext.identifier, &ext.auth_code));
+
+            match ext.data[..] {
+                // data sub-block of at least 3 bytes, with sub-block ID 1 followed by the count
+                [size, 1, lo, hi, ..] if known && size >= 3 => Some(u16::from_le_bytes([lo, hi])),
+                _ => None,
+            }
+        })
+    }
+
+    /// XMP packet from an `XMP Data` application extension with auth code `XMP`, without its magic trailer,
+    /// `None` if there's none or it wasn't kept.
+    /// The packet isn't split into sub-blocks but written as is, and the trailer gets decoders walking it as
+    /// sub-blocks back onto the terminator, so the captured data is the packet followed by the trailer.
+    pub fn xmp(&self) -> Option<&[u8]> {
+        let ext = self.app_extensions.iter().find(|ext| (&ext.identifier, &ext.auth_code) == XMP_EXTENSION)?;
+
+        // 0x01, then every byte from 0xFF down to 0x00, with the terminator after it
+        let trailer =
+            |tail: &[u8]| tail[0] == 1 && tail[1..].iter().rev().enumerate().all(|(i, &b)| b as usize == i);
+
+        Some(match ext.data.len().checked_sub(257) {
+            Some(start) if trailer(&ext.data[start..]) => &ext.data[..start],
+            // cut off at `MAX_CAPTURE`, or written without the trailer
+            _ => &ext.data,
+        })
+    }
+
+    /// Transition once enough of a fixed-size header has been collected.
+    fn fixed_field(&mut self) -> State {
+        match self.state {
+            State::Header if self.len == 13 => {
+                if !self.fixed.starts_with(b"GIF") {
+                    return State::Broken;
+                }
+
+                self.version.copy_from_slice(&self.fixed[3..6]);
+                self.background = self.fixed[11];
+                self.aspect_ratio = self.fixed[12];
+
+                // logical screen descriptor packed fields
+                match self.fixed[10] {
+                    flags if flags & 0x80 != 0 => State::GlobalPalette(3 << ((flags & 0x07) + 1)),
+                    _ => State::Introducer,
+                }
+            }
+            State::ImageDescriptor if self.len == 9 => match self.fixed[8] {
+                flags if flags & 0x80 != 0 => State::LocalPalette(3 << ((flags & 0x07) + 1)),
+                _ => State::LzwCodeSize,
+            },
+            state => state,
+        }
+    }
+}
+
+/// Starts of payloads that polyglot files append after the trailer, matched ignoring ASCII case
+const POLYGLOT_SIGNATURES: [&[u8]; 12] = [
+    // ZIP, and so JAR, APK and Office files, by a local file header or the end of an empty archive
+    b"PK\x03\x04",
+    b"PK\x05\x06",
+    b"Rar!\x1a\x07",
+    b"7z\xbc\xaf\x27\x1c",
+    b"%PDF-",
+    b"\x7fELF",
+    b"#!/",
+    b"<script",
+    b"<html",
+    b"<!doctype html",
+    b"<svg",
+    b"<?php",
+];
+
+/// Counts the bytes after the trailer, looking for [`POLYGLOT_SIGNATURES`] anywhere in them.
+#[derive(Default)]
+pub struct TrailingData {
+    pub bytes: u64,
+    /// Whether any of the signatures appeared
+    pub suspect: bool,
+    /// The last bytes fed, so a signature split across reads still matches
+    tail: Vec<u8>,
+}
+
+impl TrailingData {
+    fn feed(&mut self, buf: &[u8]) {
+        self.bytes += buf.len() as u64;
+
+        if self.suspect {
+            return;
+        }
+
+        self.tail.extend_from_slice(buf);
+        self.suspect = POLYGLOT_SIGNATURES
+            .iter()
+            .any(|signature| self.tail.windows(signature.len()).any(|w| w.eq_ignore_ascii_case(signature)));
+
+        // anything older can't be the start of a match anymore
+        let longest = POLYGLOT_SIGNATURES.iter().map(|signature| signature.len()).max().unwrap_or(0);
+        self.tail.drain(..self.tail.len().saturating_sub(longest - 1));
+    }
+}
+
+/// Follows each frame's LZW code stream just far enough to know the code width, without decompressing it.
+///
+/// Only the number of dictionary entries matters for that, so no strings are kept.
+#[derive(Default)]
+pub struct LzwWidths {
+    /// Widest code read in each frame, 0 for frames without any codes
+    pub frames: Vec<u8>,
+
+    min_size: u8,
+    width: u8,
+    next_code: u16,
+    /// The first code after a clear code doesn't add a dictionary entry
+    after_clear: bool,
+    ended: bool,
+    bits: u32,
+    nbits: u8,
+}
+
+impl LzwWidths {
+    /// Max code width of the LZW format
+    const MAX_WIDTH: u8 = 12;
+
+    fn start(&mut self, min_size: u8) {
+        // out of range sizes are a decode error anyway, just keep the widths sane
+        self.min_size = min_size.clamp(1, Self::MAX_WIDTH - 1);
+        self.ended = false;
+        self.bits = 0;
+        self.nbits = 0;
+        self.frames.push(0);
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.width = self.min_size + 1;
+        self.next_code = (1 << self.min_size) + 2;
+        self.after_clear = true;
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        if self.ended {
+            return;
+        }
+
+        for &byte in data {
+            // codes are packed least significant bit first
+            self.bits |= (byte as u32) << self.nbits;
+            self.nbits += 8;
+
+            while self.nbits >= self.width {
+                let code = (self.bits & ((1 << self.width) - 1)) as u16;
+                self.bits >>= self.width;
+                self.nbits -= self.width;
+
+                if let Some(max) = self.frames.last_mut() {
+                    *max = (*max).max(self.width);
+                }
+
+                self.code(code);
+
+                // anything after the end code is padding
+                if self.ended {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn code(&mut self, code: u16) {
+        let clear = 1 << self.min_size;
+
+        if code == clear {
+            return self.clear();
+        }
+
+        if code == clear + 1 {
+            self.ended = true;
+            return;
+        }
+
+        if std::mem::take(&mut self.after_clear) {
+            return;
+        }
+
+        // once full, the dictionary stays as is until the next clear code
+        if self.next_code < 1 << Self::MAX_WIDTH {
+            self.next_code += 1;
+
+            if self.next_code == 1 << self.width && self.width < Self::MAX_WIDTH {
+                self.width += 1;
+            }
+        }
+    }
+}
+
+/// Data sub-blocks joined together, without their size bytes
+pub(crate) fn join_sub_blocks(mut data: &[u8]) -> Vec<u8> {
+    let mut joined = Vec::with_capacity(data.len());
+
+    while let Some((&size, tail)) = data.split_first() {
+        let size = (size as usize).min(tail.len());
+        joined.extend_from_slice(&tail[..size]);
+        data = &tail[size..];
+    }
+
+    joined
+}
+
+/// Appends to a captured extension, up to [`MAX_CAPTURE`] bytes, and [`MAX_CAPTURE_TOTAL`] counting `captured`.
+fn push_capture((_, data, truncated): &mut (u64, Vec<u8>, bool), captured: &mut usize, bytes: &[u8]) {
+    let room = (MAX_CAPTURE - data.len()).min(MAX_CAPTURE_TOTAL - *captured);
+    let take = bytes.len().min(room);
+
+    *truncated |= bytes.len() > room;
+    *captured += take;
+    data.extend_from_slice(&bytes[..take]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A header without a global palette, then `count` application extensions of 16 full sub-blocks each
+    fn app_extensions(identifier: &[u8; 11], count: usize) -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00".to_vec();
+
+        for _ in 0..count {
+            gif.extend_from_slice(b"\x21\xff\x0b");
+            gif.extend_from_slice(identifier);
+
+            for _ in 0..16 {
+                gif.push(255);
+                gif.extend_from_slice(&[0xAA; 255]);
+            }
+
+            gif.push(0);
+        }
+
+        gif.push(0x3B);
+        gif
+    }
+
+    #[test]
+    fn unrequested_extensions_arent_kept() {
+        let mut scanner = Scanner::default();
+        scanner.feed(b"GIF89a\x01\x00\x01\x00\x00\x00\x00\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+        scanner.feed(&app_extensions(b"JUNKJUNK1.0", 100)[13..]);
+
+        // only the loop extension the summary needs
+        assert_eq!(scanner.app_extensions.len(), 1);
+        assert_eq!(scanner.loop_count(), Some(0));
+        assert_eq!(scanner.captured, 4);
+        assert!(scanner.trailer.is_some());
+    }
+
+    #[test]
+    fn xmp_is_only_kept_when_asked_for() {
+        let gif = app_extensions(b"XMP DataXMP", 1);
+
+        let mut scanner = Scanner::default();
+        scanner.feed(&gif);
+
+        assert!(scanner.has_xmp);
+        assert_eq!(scanner.xmp(), None);
+        assert_eq!(scanner.captured, 0);
+
+        let mut scanner = Scanner {
+            capture_xmp: true,
+            ..Scanner::default()
+        };
+        scanner.feed(&gif);
+
+        assert!(scanner.has_xmp);
+        assert!(scanner.xmp().is_some());
+    }
+
+    #[test]
+    fn color_profiles_arent_kept() {
+        let mut scanner = Scanner::default();
+        scanner.feed(&app_extensions(b"ICCRGBG1012", 1));
+
+        assert!(scanner.has_color_profile);
+        assert!(scanner.app_extensions.is_empty());
+        assert_eq!(scanner.captured, 0);
+    }
+
+    #[test]
+    fn captures_share_a_budget() {
+        let mut scanner = Scanner {
+            capture_app_extensions: true,
+            ..Scanner::default()
+        };
+
+        // 4 KiB each, twice the budget in all
+        let gif = app_extensions(b"JUNKJUNK1.0", 2000);
+        scanner.feed(&gif);
+
+        let kept: usize = scanner.app_extensions.iter().map(|ext| ext.data.len()).sum();
+
+        assert!(kept <= MAX_CAPTURE_TOTAL);
+        assert!(scanner.captured <= MAX_CAPTURE_TOTAL);
+        assert!(scanner.app_extensions.last().unwrap().truncated);
+        assert!(!scanner.app_extensions[0].truncated);
+        assert!(scanner.trailer.is_some());
+    }
+}
@@ -0,0 +1,306 @@
+//! Long-lived probing over a line-based JSON protocol, for `--stdio-server`, over length-prefixed payloads,
+//! for `serve --stdio`, or over a Unix domain socket, for `serve --socket`.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error_record, output::ErrorRecord, probe, rejected_record, GifProbe, JsonOptions, ProbeError, ProbeOptions,
+};
+
+/// Most connections the socket and HTTP servers handle at once, each with its own thread and decoder
+pub(crate) const MAX_CONNECTIONS: usize = 64;
+
+/// Connections that send or take nothing for this long are closed, so stalled clients don't hold on to their
+/// threads
+pub(crate) const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Counts the connections being handled, up to [`MAX_CONNECTIONS`]
+#[derive(Default)]
+pub(crate) struct ConnectionLimit {
+    active: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl ConnectionLimit {
+    /// Waits until fewer than the limit are being handled, then counts one more until the slot is dropped
+    pub(crate) fn acquire(&self) -> Slot<'_> {
+        let mut active = self.active.lock().unwrap();
+
+        while *active >= MAX_CONNECTIONS {
+            active = self.freed.wait(active).unwrap();
+        }
+
+        *active += 1;
+        Slot(self)
+    }
+
+    /// Counts one more if fewer than the limit are being handled
+    #[cfg(feature = "http")]
+    pub(crate) fn try_acquire(&self) -> Option<Slot<'_>> {
+        let mut active = self.active.lock().unwrap();
+
+        (*active < MAX_CONNECTIONS).then(|| {
+            *active += 1;
+            Slot(self)
+        })
+    }
+}
+
+/// One connection counted by a [`ConnectionLimit`]
+pub(crate) struct Slot<'a>(&'a ConnectionLimit);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// What a request line asks to probe
+enum Request {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+/// Fields of a request line, any others are ignored to leave room for things like request IDs
+#[derive(Deserialize)]
+struct RequestLine {
+    path: Option<PathBuf>,
+    base64: Option<String>,
+}
+
+impl Request {
+    fn parse(line: &str) -> Result<Request, String> {
+        let request: RequestLine = serde_json::from_str(line).map_err(|e| e.to_string())?;
+
+        match (request.path, request.base64) {
+            (Some(path), None) => Ok(Request::Path(path)),
+            (None, Some(base64)) => Ok(Request::Bytes(base64_decode(&base64).ok_or("invalid base64")?)),
+            (Some(_), Some(_)) => Err("expected only one of `path` or `base64`".to_owned()),
+            (None, None) => Err("expected `path` or `base64`".to_owned()),
+        }
+    }
+}
+
+/// Answers each line of `input` with one line of `output`, until `input` ends.
+///
+/// Requests are `{"path": ...}` to probe a file or `{"base64": ...}` to probe the given bytes.
+/// Responses are the probe's JSON, or an object with an `error` for requests that failed or didn't parse.
+/// Blank lines are skipped.
+pub fn serve_json_lines<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    options: &ProbeOptions,
+    json_options: &JsonOptions,
+) -> io::Result<()> {
+    // every response has to fit on its line
+    let json_options = &JsonOptions {
+        pretty: false,
+        ..*json_options
+    };
+
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match Request::parse(&line) {
+            Ok(Request::Path(path)) => match File::open(&path) {
+                Ok(file) => {
+                    // without metadata, reading still stops at the limit
+                    let len = file.metadata().map_or(0, |metadata| metadata.len());
+
+                    match options.check_file_size(len).and_then(|()| probe(BufReader::new(file), options)) {
+                        Ok(probe) => probe.to_json_record(&path, json_options),
+                        Err(e) => match e.rejection() {
+                            Some(rejected) => rejected_record(Some(&path), rejected, &e),
+                            None => error_record(&path, &e),
+                        },
+                    }
+                }
+                Err(e) => error_record(&path, &e),
+            },
+            Ok(Request::Bytes(bytes)) => response(probe(&bytes[..], options), json_options),
+            Err(e) => error_response(&format_args!("invalid request: {e}")),
+        };
+
+        // the client waits for each response before sending the next request
+        writeln!(output, "{response}")?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Answers each payload of `input` with one line of `output`, until `input` ends.
+///
+/// Each payload is the bytes to probe, after their length as a 4-byte big-endian integer. Responses are the
+/// probe's JSON, or an object with an `error` for payloads that failed. Payloads are probed as they're read,
+/// without being buffered whole, and whatever probing didn't need is skipped to get to the next length.
+/// Fails on a read error or a payload cut short, since the lengths can't be found after that.
+pub fn serve_length_prefixed<R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
+    options: &ProbeOptions,
+    json_options: &JsonOptions,
+) -> io::Result<()> {
+    let json_options = &JsonOptions {
+        pretty: false,
+        ..*json_options
+    };
+
+    while let Some(len) = read_length(&mut input)? {
+        let mut payload = (&mut input).take(len);
+
+        let result = options.check_file_size(len).and_then(|()| probe(&mut payload, options));
+        let response = response(result, json_options);
+
+        io::copy(&mut payload, &mut io::sink())?;
+
+        if payload.limit() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "input ended in the middle of a payload",
+            ));
+        }
+
+        writeln!(output, "{response}")?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Answers each connection accepted on a Unix domain socket bound to `path` with one line of JSON, on its own
+/// thread, until accepting fails.
+///
+/// A connection sends the bytes to probe and shuts down its writing half, then gets the probe's JSON or an
+/// object with an `error`, after which the connection is closed. The rest of the input is read before answering,
+/// so the client can write it all without a broken pipe, unless it's over the maximum file size. Binding fails if
+/// something already exists at `path`, like the socket of a previous run, which has to be removed first.
+///
+/// At most [`MAX_CONNECTIONS`] are handled at once, later ones wait to be accepted until one of those is done.
+/// A connection that sends or takes nothing for [`IDLE_TIMEOUT`] is answered with an error, or dropped.
+#[cfg(unix)]
+pub fn serve_unix_socket(
+    path: &std::path::Path,
+    options: &ProbeOptions,
+    json_options: &JsonOptions,
+) -> io::Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    let listener = UnixListener::bind(path)?;
+    let json_options = &JsonOptions {
+        pretty: false,
+        ..*json_options
+    };
+
+    let limit = &ConnectionLimit::default();
+
+    std::thread::scope(|s| loop {
+        let slot = limit.acquire();
+        let mut stream = listener.accept()?.0;
+
+        s.spawn(move || {
+            let _slot = slot;
+
+            // a client that hung up or stalled can't be answered anyway
+            if stream.set_read_timeout(Some(IDLE_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(IDLE_TIMEOUT)).is_err()
+            {
+                return;
+            }
+
+            let mut input = BufReader::new(&stream);
+            let result = probe(&mut input, options);
+
+            if !matches!(result, Err(ProbeError::FileTooLarge)) {
+                let _ = io::copy(&mut input, &mut io::sink());
+            }
+
+            let _ = writeln!(stream, "{}", response(result, json_options));
+        });
+    })
+}
+
+/// The probe's JSON, or a record of why it failed, for anything probed without a path
+pub(crate) fn response(result: Result<GifProbe, ProbeError>, json_options: &JsonOptions) -> String {
+    match result {
+        Ok(probe) => probe.to_json(json_options),
+        Err(e) => match e.rejection() {
+            Some(rejected) => rejected_record(None, rejected, &e),
+            None => error_response(&e),
+        },
+    }
+}
+
+/// Reads the length before a payload, `None` if the input ends before it
+fn read_length<R: Read>(input: &mut R) -> io::Result<Option<u64>> {
+    let mut header = [0; 4];
+    let mut filled = 0;
+
+    while filled < header.len() {
+        match input.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input ended in the middle of a length",
+                ));
+            }
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Some(u32::from_be_bytes(header) as u64))
+}
+
+/// `{"error": ...}` line for a request without a path
+pub(crate) fn error_response(error: &dyn std::fmt::Display) -> String {
+    ErrorRecord::new(None, None, error).to_json()
+}
+
+/// Standard base64, with or without padding
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=').as_bytes();
+
+    // a lone trailing character can't encode a whole byte
+    if input.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+
+    for chunk in input.chunks(4) {
+        let mut n = 0u32;
+
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+
+            n |= (value as u32) << (18 - 6 * i);
+        }
+
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+
+    Some(out)
+}
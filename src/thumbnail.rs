@@ -0,0 +1,104 @@
+//! Frame PNG export for `--thumbnail` and `extract`.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use gif::Frame;
+
+use crate::composite::Canvas;
+
+/// Which frame [`ProbeOptions::thumbnail`](crate::ProbeOptions::thumbnail) writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePosition {
+    /// The frame at this 0-based index
+    Index(u64),
+    /// The frame showing this many milliseconds into the first loop, with delays as browsers play them
+    Millis(u64),
+}
+
+impl Default for FramePosition {
+    fn default() -> Self {
+        FramePosition::Index(0)
+    }
+}
+
+/// Composites frames onto a transparent canvas the size of the logical screen until the requested one is drawn,
+/// then writes the canvas as an 8-bit RGBA PNG, so it matches what a player shows at that point.
+pub struct Thumbnail {
+    path: PathBuf,
+    position: FramePosition,
+    width: u16,
+    height: u16,
+    canvas: Canvas,
+    frames: u64,
+    elapsed: u64,
+    written: bool,
+}
+
+impl Thumbnail {
+    pub fn new(path: &Path, position: FramePosition, width: u16, height: u16) -> Self {
+        Thumbnail {
+            path: path.to_owned(),
+            position,
+            width,
+            height,
+            canvas: Canvas::new(width, height),
+            frames: 0,
+            elapsed: 0,
+            written: false,
+        }
+    }
+
+    /// Whether frames still need to be decoded in full and passed to [`add_frame`](Self::add_frame)
+    pub fn pending(&self) -> bool {
+        !self.written
+    }
+
+    /// Draws a fully decoded frame shown for `delay` milliseconds, writing the PNG if it's the requested one.
+    pub fn add_frame(&mut self, frame: &Frame, global_palette: Option<&[u8]>, delay: u64) -> io::Result<()> {
+        if self.written {
+            return Ok(());
+        }
+
+        self.canvas.draw(frame, global_palette);
+
+        let requested = match self.position {
+            FramePosition::Index(index) => self.frames == index,
+            FramePosition::Millis(millis) => self.elapsed + delay > millis,
+        };
+
+        self.frames += 1;
+        self.elapsed += delay;
+
+        match requested {
+            true => self.write(),
+            false => Ok(()),
+        }
+    }
+
+    /// A timestamp past the end of the animation shows the last frame, held after it finishes playing once.
+    /// Nothing is written for an index past the last frame.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.position {
+            FramePosition::Millis(_) if !self.written && self.frames > 0 => self.write(),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&mut self) -> io::Result<()> {
+        self.written = true;
+
+        let file = BufWriter::new(File::create(&self.path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(self.canvas.pixels()).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,15 @@
+//! JavaScript interface for the `wasm` feature, so the same checks can run in a browser before uploading.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{JsonOptions, ProbeOptions};
+
+/// Probes the bytes of a whole file like the binary does without flags, returning the same record as an object.
+/// Throws an `Error` with the reason if probing fails.
+#[wasm_bindgen(js_name = probe)]
+pub fn probe_bytes(bytes: &[u8]) -> Result<JsValue, JsError> {
+    let probe = crate::probe(bytes, &ProbeOptions::default()).map_err(|e| JsError::new(&e.to_string()))?;
+
+    // the same field names and values as the binary's output, without a serializer for every type
+    js_sys::JSON::parse(&probe.to_json(&JsonOptions::default())).map_err(|_| JsError::new("invalid probe JSON"))
+}
@@ -0,0 +1,194 @@
+//! Still and animated WebP probing for the `webp` feature, filling in the same summary fields as for a GIF.
+//!
+//! Frame counts, delays and looping come straight from the RIFF chunks, so the image data is only decoded
+//! when the file declares an alpha channel, to find out whether any pixel actually uses it.
+
+use std::io;
+
+use image_webp::WebPDecoder;
+
+use crate::{
+    probe::{Codec, GifProbe, STILL},
+    ProbeError, ProbeOptions,
+};
+
+/// Whether the start of the input is a WebP's `RIFF` header, which needs the first 12 bytes
+pub fn sniff(magic: &[u8]) -> bool {
+    magic.len() >= 12 && magic[..4] == *b"RIFF" && magic[8..12] == *b"WEBP"
+}
+
+pub fn probe(data: &[u8], options: &ProbeOptions) -> Result<GifProbe, ProbeError> {
+    let mut decoder = WebPDecoder::new(io::Cursor::new(data))?;
+    decoder.set_memory_limit(options.memory_limit().get() as usize);
+
+    let (width, height) = decoder.dimensions();
+
+    if matches!(options.max_pixels, Some(m) if m < (width as u64 * height as u64)) {
+        return Err(ProbeError::TooLarge);
+    }
+
+    let container = Container::parse(data);
+
+    let mut probe = GifProbe {
+        codec: container.codec,
+        // the canvas can't be larger than 16384x16384
+        width: width as u16,
+        height: height as u16,
+        has_color_profile: container.has_color_profile,
+        loop_count: container.loop_count.filter(|_| decoder.is_animated()),
+        has_xmp: container.has_xmp,
+        ..GifProbe::default()
+    };
+
+    match decoder.is_animated() {
+        true => probe.add_delays(container.delays.iter().map(|&delay| (1, delay))),
+        false => probe.add_delays(STILL),
+    }
+
+    if !decoder.has_alpha() {
+        probe.alpha_coverage = (!probe.frameless).then_some(0.0);
+    } else {
+        let size = decoder.output_buffer_size().ok_or(ProbeError::TooLarge)?;
+
+        // the decoder's own limit doesn't cover the buffer it decodes into
+        if size as u64 > options.memory_limit().get() {
+            return Err(ProbeError::TooLarge);
+        }
+
+        let mut buffer = vec![0; size];
+        probe.memory_peak = buffer.len() as u64;
+
+        for index in 0..probe.frames {
+            options.check_cancelled()?;
+
+            if decoder.is_animated() {
+                decoder.read_frame(&mut buffer)?;
+            } else {
+                decoder.read_image(&mut buffer)?;
+            }
+
+            let transparent = buffer.chunks_exact(4).filter(|px| px[3] < 255).count();
+
+            if index == 0 {
+                probe.alpha_coverage =
+                    (!buffer.is_empty()).then(|| transparent as f64 / (buffer.len() / 4) as f64);
+            }
+
+            if transparent > 0 {
+                probe.alpha = true;
+                break;
+            }
+        }
+    }
+
+    Ok(probe)
+}
+
+/// What's read straight from the RIFF chunks, without decoding anything
+#[derive(Default)]
+struct Container {
+    /// Compression of the first frame
+    codec: Codec,
+    /// Delay of each animation frame from its `ANMF`, rounded from milliseconds to centiseconds
+    delays: Vec<u16>,
+    /// From the `ANIM` chunk, where 0 means forever
+    loop_count: Option<u16>,
+    has_color_profile: bool,
+    has_xmp: bool,
+}
+
+impl Container {
+    /// The decoder has already validated the container, so this only reads what's there.
+    fn parse(data: &[u8]) -> Self {
+        let mut container = Container::default();
+        let mut codec = None;
+
+        for (fourcc, payload) in chunks(data.get(12..).unwrap_or_default()) {
+            match fourcc {
+                b"VP8 " | b"VP8L" => {
+                    codec = codec.or(image_codec(fourcc));
+                }
+                b"ANIM" => {
+                    container.loop_count = payload.get(4..6).map(|count| u16::from_le_bytes([count[0], count[1]]));
+                }
+                // offsets and size, then a 24-bit duration, flags and the frame's own chunks
+                b"ANMF" if payload.len() >= 16 => {
+                    let ms = u32::from_le_bytes([payload[12], payload[13], payload[14], 0]);
+                    container.delays.push(((ms + 5) / 10).min(u16::MAX as u32) as u16);
+                    codec = codec.or_else(|| chunks(&payload[16..]).find_map(|(fourcc, _)| image_codec(fourcc)));
+                }
+                b"ICCP" => container.has_color_profile = true,
+                b"XMP " => container.has_xmp = true,
+                _ => {}
+            }
+        }
+
+        container.codec = codec.unwrap_or(Codec::Vp8);
+        container
+    }
+}
+
+fn image_codec(fourcc: &[u8]) -> Option<Codec> {
+    match fourcc {
+        b"VP8 " => Some(Codec::Vp8),
+        b"VP8L" => Some(Codec::Vp8l),
+        _ => None,
+    }
+}
+
+/// RIFF chunks as their FourCC and payload, stopping at a truncated one
+fn chunks(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let (header, rest) = (data.get(..8)?, &data[8..]);
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let payload = rest.get(..size)?;
+
+        // payloads are padded to an even size
+        data = rest.get(size + size % 2..).unwrap_or_default();
+
+        Some((&header[..4], payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use super::*;
+
+    const ANIMATED: &[u8] = include_bytes!("../tests/fixtures/animated.webp");
+
+    #[test]
+    fn animated() {
+        // 4x4 lossless frames, opaque for 70 ms and then half transparent for 120 ms, looping 3 times
+        let probe = crate::probe(ANIMATED, &ProbeOptions::default()).unwrap();
+
+        assert_eq!(probe.codec, Codec::Vp8l);
+        assert_eq!((probe.width, probe.height), (4, 4));
+        assert_eq!(probe.frames, 2);
+        assert_eq!(probe.duration, 19);
+        assert_eq!(probe.loop_count, Some(3));
+        assert!(probe.alpha);
+        assert_eq!(probe.alpha_coverage, Some(0.0));
+    }
+
+    #[test]
+    fn alpha_buffer_counts_towards_max_memory() {
+        let options = ProbeOptions {
+            max_memory: NonZeroU64::new(32),
+            ..ProbeOptions::default()
+        };
+
+        assert!(matches!(crate::probe(ANIMATED, &options), Err(ProbeError::TooLarge)));
+    }
+
+    #[test]
+    fn chunks_stop_at_a_truncated_one() {
+        // cut off in the second frame
+        let container = Container::parse(&ANIMATED[..ANIMATED.len() - 8]);
+
+        assert_eq!(container.delays, [7]);
+        assert_eq!(container.loop_count, Some(3));
+        assert_eq!(container.codec, Codec::Vp8l);
+    }
+}